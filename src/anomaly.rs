@@ -0,0 +1,69 @@
+// SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+/// Weight of the newest value in the exponentially weighted moving average
+const ALPHA: f64 = 0.3;
+
+/// Minimum number of values to establish a baseline
+const MIN_VALUES: usize = 5;
+
+/// Minimum standard deviation as share of the mean. A deviation below this share is never an anomaly
+/// e.g. the first growth of a repository with a constant size.
+const MIN_RELATIVE_STDDEV: f64 = 0.01;
+
+/// The exponentially weighted moving average and standard deviation of a series of values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Baseline {
+    /// Moving average
+    pub(crate) mean: f64,
+    /// Moving standard deviation (at least the minimum relative standard deviation)
+    pub(crate) stddev: f64,
+}
+
+impl Baseline {
+    /// The baseline of the `values` with the oldest first or `None` with too few values
+    pub(crate) fn new(values: impl IntoIterator<Item = f64>) -> Option<Self> {
+        let mut values = values.into_iter();
+        let mut mean = values.next()?;
+        let mut variance = 0.0;
+        let mut count = 1;
+        for value in values {
+            let delta = value - mean;
+            mean += ALPHA * delta;
+            variance = (1.0 - ALPHA) * ALPHA.mul_add(delta * delta, variance);
+            count += 1;
+        }
+        (count >= MIN_VALUES).then(|| Self {
+            mean,
+            stddev: variance.sqrt().max(mean.abs() * MIN_RELATIVE_STDDEV),
+        })
+    }
+
+    /// The deviation of the `value` from the mean in standard deviations (positive or negative)
+    pub(crate) fn sigma(&self, value: f64) -> f64 {
+        (value - self.mean) / self.stddev
+    }
+}
+
+mod tests {
+    #[test]
+    fn baseline() {
+        use crate::anomaly::Baseline;
+
+        assert_eq!(Baseline::new([100.0; 4]), None);
+
+        // A constant series has no deviation and a minimum relative standard deviation
+        #[allow(clippy::unwrap_used)]
+        let constant = Baseline::new([100.0; 5]).unwrap();
+        assert_eq!((constant.mean, constant.stddev), (100.0, 1.0));
+        assert!((constant.sigma(100.5) - 0.5).abs() < 1e-9);
+        assert!((constant.sigma(95.0) + 5.0).abs() < 1e-9);
+
+        // A steady growth is the baseline, a sudden jump or drop is an anomaly
+        #[allow(clippy::unwrap_used)]
+        let growing = Baseline::new((0..20).map(|i| 1000.0 + f64::from(i) * 10.0)).unwrap();
+        assert!(growing.sigma(1200.0).abs() < 3.0);
+        assert!(growing.sigma(2000.0) > 3.0);
+        assert!(growing.sigma(500.0) < -3.0);
+    }
+}
@@ -1,11 +1,14 @@
 // SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use anyhow::{bail, Context, Result};
-use std::{ffi::OsStr, path::PathBuf};
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
 
 pub use crate::borg_json::*;
-use crate::Repository;
+use crate::{cli, repository::MODIFYING_CHECK_OPTIONS, Repository};
 
 /// All borg timestamps are UTC
 pub const BORG_TZ: &str = "UTC";
@@ -14,7 +17,8 @@ pub const BORG_TZ: &str = "UTC";
 const BORG_DEFAULT_ENV: [(&str, &str); 2] = [("LC_ALL", "C.UTF-8"), ("TZ", BORG_TZ)];
 /// Required default Borg common args
 const BORG_COMMON_ARGS: [&str; 0] = [];
-//const BORG_COMMON_ARGS: [&str; 1] = ["--log-json"];
+/// Borg common args to emit structured log messages on stderr
+const BORG_LOG_JSON_ARGS: [&str; 1] = ["--log-json"];
 
 /// Wrapper to hold BORG_* env vars as key=value pairs
 pub type Env = std::collections::BTreeMap<String, String>;
@@ -24,84 +28,692 @@ pub struct Output {
     pub status: std::process::ExitStatus,
     pub stdout: String,
     pub stderr: String,
+    /// Structured log messages (only with `--log-json`)
+    pub messages: Vec<LogMessage>,
     /// command execution time
     pub duration: jiff::SignedDuration,
+    /// Peak memory usage (max RSS) of the process in bytes, when it was measured
+    pub peak_rss: Option<u64>,
+}
+
+impl Output {
+    /// Split the `--log-json` records from stderr into `messages`.
+    /// Lines which are not valid JSON (e.g. a Python traceback) remain in `stderr`.
+    fn parse_log_json(&mut self) {
+        let mut stderr = String::new();
+        for line in self.stderr.lines() {
+            match serde_json::from_str::<LogLine>(line) {
+                Ok(LogLine::LogMessage(message)) => self.messages.push(message),
+                Ok(LogLine::ProgressPercent(_) | LogLine::Other) => {}
+                Err(_) => {
+                    stderr.push_str(line);
+                    stderr.push('\n');
+                }
+            }
+        }
+        self.stderr = stderr;
+    }
+
+    /// True if borg failed to acquire the repository lock
+    fn is_locked(&self) -> bool {
+        self.messages.iter().any(|m| {
+            m.msgid
+                .as_deref()
+                .is_some_and(|id| ["LockTimeout", "LockFailed"].contains(&id))
+        }) || self.stderr.contains("Failed to create/acquire the lock")
+    }
+
+    /// An error message if borg was terminated by a signal e.g. killed by the OOM killer
+    pub fn termination(&self) -> Option<String> {
+        use std::os::unix::process::ExitStatusExt;
+        self.status.signal().map(termination_message)
+    }
+
+    /// True if borg failed to connect to the (remote) repository
+    pub fn is_unreachable(&self) -> bool {
+        self.messages.iter().any(|m| {
+            m.msgid
+                .as_deref()
+                .is_some_and(|id| id.starts_with("ConnectionClosed"))
+        }) || CONNECTION_FAILURES.iter().any(|p| self.stderr.contains(p))
+    }
+
+    /// True if the repository does not exist or the path holds no borg repository
+    fn is_not_initialized(&self) -> bool {
+        self.messages.iter().any(|m| {
+            m.msgid.as_deref().is_some_and(|id| {
+                ["Repository.DoesNotExist", "Repository.InvalidRepository"].contains(&id)
+            })
+        }) || NOT_INITIALIZED.iter().any(|p| self.stderr.contains(p))
+    }
+
+    /// All error messages: Log messages of level `ERROR` or above, the unparsed stderr
+    /// and the terminating signal
+    pub fn errors(&self) -> String {
+        let termination = self.termination();
+        self.messages
+            .iter()
+            .filter(|m| m.levelname >= Level::Error)
+            .map(|m| m.message.as_str())
+            .chain(std::iter::once(self.stderr.trim_end()))
+            .chain(termination.as_deref())
+            .filter(|m| !m.is_empty())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+/// Describe the termination of borg by a `signal` e.g. "borg terminated by signal 9 (SIGKILL, possibly OOM)"
+fn termination_message(signal: i32) -> String {
+    use signal_hook::consts::signal::{
+        SIGABRT, SIGBUS, SIGFPE, SIGHUP, SIGILL, SIGINT, SIGKILL, SIGPIPE, SIGQUIT, SIGSEGV,
+        SIGTERM,
+    };
+    let name = match signal {
+        SIGHUP => "SIGHUP",
+        SIGINT => "SIGINT",
+        SIGQUIT => "SIGQUIT",
+        SIGILL => "SIGILL",
+        SIGABRT => "SIGABRT",
+        SIGBUS => "SIGBUS",
+        SIGFPE => "SIGFPE",
+        SIGKILL => "SIGKILL, possibly OOM",
+        SIGSEGV => "SIGSEGV",
+        SIGPIPE => "SIGPIPE",
+        SIGTERM => "SIGTERM",
+        _ => "unknown",
+    };
+    format!("borg terminated by signal {signal} ({name})")
+}
+
+/// Maximum number of characters of the borg output quoted in an error
+const SNIPPET_WIDTH: usize = 200;
+
+/// Describe the failure to parse the JSON `stdout` of a successful `borg {command}`.
+/// A truncated response (e.g. of an interrupted borg) is told apart from an invalid one.
+fn json_error_message(command: &str, stdout: &str, e: &serde_json::Error) -> String {
+    let problem = if stdout.trim().is_empty() {
+        "is empty"
+    } else if e.is_eof() {
+        "is truncated"
+    } else {
+        "is not valid JSON"
+    };
+    let snippet: String = stdout.chars().take(SNIPPET_WIDTH).collect();
+    let ellipsis = if stdout.chars().count() > SNIPPET_WIDTH {
+        "…"
+    } else {
+        ""
+    };
+    format!(
+        "borg {command} succeeded but its response {problem} ({e}). Output: {}{ellipsis}",
+        snippet.trim_end()
+    )
+}
+
+/// The repository is locked by another borg process
+#[derive(Debug)]
+pub struct Locked(pub String);
+
+impl std::fmt::Display for Locked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Locked {}
+
+/// Messages on stderr indicating a connection failure e.g. of ssh to a remote repository
+const CONNECTION_FAILURES: [&str; 7] = [
+    "Connection closed by remote host",
+    "Connection refused",
+    "Connection reset",
+    "Connection timed out",
+    "Could not resolve hostname",
+    "Network is unreachable",
+    "No route to host",
+];
+
+/// Messages on stderr indicating a missing repository e.g. a freshly provisioned remote path
+const NOT_INITIALIZED: [&str; 2] = ["is not a valid repository", "does not exist"];
+
+/// The repository is unreachable: borg failed to connect or was terminated after a timeout
+#[derive(Debug)]
+pub struct Unreachable(pub String);
+
+impl std::fmt::Display for Unreachable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Unreachable {}
+
+/// Parse the output of `borg --version` e.g. "borg 1.2.8" or "borg2 2.0.0b14".
+/// A beta or release candidate suffix is treated as pre-release.
+fn parse_version(output: &str) -> Option<semver::Version> {
+    let version = output.split_whitespace().last()?;
+    let split = version
+        .char_indices()
+        .find(|(_, c)| !c.is_ascii_digit() && *c != '.')
+        .map_or(version.len(), |(i, _)| i);
+    let (release, pre) = version.split_at(split);
+    let release = release.trim_end_matches('.');
+    let release = match release.matches('.').count() {
+        0 => format!("{release}.0.0"),
+        1 => format!("{release}.0"),
+        _ => release.to_string(),
+    };
+    let pre = pre.trim_start_matches(['-', '.']);
+    if pre.is_empty() {
+        semver::Version::parse(&release).ok()
+    } else {
+        semver::Version::parse(&format!("{release}-{pre}")).ok()
+    }
+}
+
+/// Query the version of a borg binary via `borg --version`
+pub fn version(bin: &Path) -> Result<semver::Version> {
+    let output = std::process::Command::new(bin)
+        .envs(BORG_DEFAULT_ENV)
+        .arg("--version")
+        .output()
+        .context(format!("Failed to execute borg binary: `{bin:?}`"))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_version(&stdout).context(format!(
+        "Failed to parse the version of borg binary `{bin:?}`: {}",
+        stdout.trim()
+    ))
+}
+
+/// True if the file name looks like a borg binary e.g. `borg`, `borg1`, `borg2` or `borg-1.2`
+fn is_borg_binary_name(name: &str) -> bool {
+    name.strip_prefix("borg").is_some_and(|suffix| {
+        suffix
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == '-')
+            && !suffix.starts_with('.')
+    })
+}
+
+/// Find the first borg binary on the PATH with a version satisfying `req`
+pub fn find_binary(req: &semver::VersionReq) -> Result<PathBuf> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let mut rejected = Vec::new();
+    for dir in std::env::split_paths(&path) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut candidates: Vec<PathBuf> = entries
+            .filter_map(std::result::Result::ok)
+            .filter(|e| e.file_name().to_str().is_some_and(is_borg_binary_name))
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect();
+        candidates.sort();
+        for candidate in candidates {
+            match version(&candidate) {
+                Ok(v) if req.matches(&v) => return Ok(candidate),
+                Ok(v) => rejected.push(format!("{candidate:?} ({v})")),
+                Err(_) => rejected.push(format!("{candidate:?} (unknown version)")),
+            }
+        }
+    }
+    if rejected.is_empty() {
+        bail!("No borg binary found on the PATH")
+    }
+    bail!(
+        "No borg binary on the PATH satisfies the version requirement '{req}': {}",
+        rejected.join(", ")
+    )
+}
+
+/// The borg binaries resolved by their version requirement. Each binary is resolved once per run.
+#[derive(Default)]
+pub struct Binaries(std::collections::BTreeMap<(Option<PathBuf>, String), Result<PathBuf, String>>);
+
+impl Binaries {
+    /// The borg binary of the `repo`: The configured binary (default: `borg`) or the first borg on
+    /// the PATH, which satisfies the version requirement. Only a version requirement runs `borg --version`.
+    pub fn resolve(&mut self, repo: &Repository) -> Result<PathBuf> {
+        let Some(req) = &repo.borg_version_req else {
+            return Ok(repo.borg_binary.clone().unwrap_or(PathBuf::from("borg")));
+        };
+        let key = (repo.borg_binary.clone(), req.to_string());
+        self.0
+            .entry(key)
+            .or_insert_with(|| {
+                select_binary(repo.borg_binary.as_deref(), req).map_err(|e| format!("{e:#}"))
+            })
+            .clone()
+            .map_err(|e| anyhow!(e))
+    }
+}
+
+/// Select the `bin` or find a borg binary on the PATH satisfying the version requirement `req`
+fn select_binary(bin: Option<&Path>, req: &semver::VersionReq) -> Result<PathBuf> {
+    let Some(bin) = bin else {
+        return find_binary(req).context("Cannot find borg");
+    };
+    let version = version(bin)?;
+    if !req.matches(&version) {
+        bail!(
+            "The borg binary {bin:?} ({version}) does not satisfy the version requirement '{req}'"
+        );
+    }
+    Ok(bin.to_path_buf())
+}
+
+/// Reap the `child`, when it exited, and return its exit status with its peak memory usage (max RSS) in bytes.
+/// Returns `None` while the `child` is running.
+#[cfg(target_os = "linux")]
+fn try_wait_rusage(
+    child: &mut std::process::Child,
+) -> std::io::Result<Option<(std::process::ExitStatus, Option<u64>)>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = libc::pid_t::try_from(child.id()).map_err(std::io::Error::other)?;
+    let mut status = 0;
+    let mut rusage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    // SAFETY: `rusage` is zero initialized and only filled by `wait4`
+    let (reaped, rusage) = unsafe {
+        let reaped = libc::wait4(pid, &mut status, libc::WNOHANG, rusage.as_mut_ptr());
+        (reaped, rusage.assume_init())
+    };
+    match reaped {
+        0 => Ok(None),
+        -1 => Err(std::io::Error::last_os_error()),
+        // Linux reports the max RSS in kilobytes
+        _ => Ok(Some((
+            std::process::ExitStatus::from_raw(status),
+            Some(u64::try_from(rusage.ru_maxrss).unwrap_or_default() * 1024),
+        ))),
+    }
+}
+
+/// Reap the `child`, when it exited, and return its exit status. The peak memory usage is not measured
+/// as the unit of the max RSS differs between the platforms.
+/// Returns `None` while the `child` is running.
+#[cfg(not(target_os = "linux"))]
+fn try_wait_rusage(
+    child: &mut std::process::Child,
+) -> std::io::Result<Option<(std::process::ExitStatus, Option<u64>)>> {
+    Ok(child.try_wait()?.map(|status| (status, None)))
+}
+
+/// Wait for the `child` to exit and collect its output with its peak memory usage in bytes (if measured).
+/// The `child` is killed after the `timeout`.
+/// Each progress message on stderr is passed to `progress` while waiting.
+fn wait_with(
+    mut child: std::process::Child,
+    timeout: Option<std::time::Duration>,
+    progress: Option<&dyn Fn(&str)>,
+) -> Result<(std::process::Output, Option<u64>)> {
+    use std::io::{BufRead, BufReader, Read};
+    use std::sync::mpsc::Sender;
+
+    // Drain the pipes in the background to not block the child on a full pipe.
+    // The progress messages of a pipe are sent line by line to the `sender`.
+    fn drain<R: Read + Send + 'static>(
+        pipe: Option<R>,
+        sender: Option<Sender<String>>,
+    ) -> std::thread::JoinHandle<std::io::Result<Vec<u8>>> {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(pipe) = pipe {
+                let mut reader = BufReader::new(pipe);
+                let mut line = Vec::new();
+                while reader.read_until(b'\n', &mut line)? > 0 {
+                    if let (Some(sender), Ok(LogLine::ProgressPercent(p))) =
+                        (&sender, serde_json::from_slice::<LogLine>(&line))
+                    {
+                        // The receiver is gone, when borg was terminated
+                        let _ = p.message.map(|message| sender.send(message));
+                    }
+                    buf.append(&mut line);
+                }
+            }
+            Ok(buf)
+        })
+    }
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let stdout = drain(child.stdout.take(), None);
+    let stderr = drain(child.stderr.take(), progress.map(|_| sender));
+
+    let start = std::time::Instant::now();
+    let (status, peak_rss) = loop {
+        if let Some(progress) = progress {
+            for message in receiver.try_iter() {
+                progress(&message);
+            }
+        }
+        if let Some(status) = try_wait_rusage(&mut child)? {
+            break status;
+        }
+        if let Some(timeout) = timeout.filter(|t| start.elapsed() > *t) {
+            child.kill()?;
+            child.wait()?;
+            return Err(Unreachable(format!(
+                "borg was terminated after the timeout of {}",
+                crate::format::human_duration(jiff::SignedDuration::try_from(timeout)?)
+            ))
+            .into());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    };
+    let join = |handle: std::thread::JoinHandle<std::io::Result<Vec<u8>>>| {
+        handle
+            .join()
+            .map_err(|_| anyhow!("Failed to read the borg output"))?
+            .context("Failed to read the borg output")
+    };
+    Ok((
+        std::process::Output {
+            status,
+            stdout: join(stdout)?,
+            stderr: join(stderr)?,
+        },
+        peak_rss,
+    ))
 }
 
 /// Response from of `borg check` command
 pub type Check = Output;
 
+/// Response from of `borg prune` command
+pub type Prune = Output;
+
+/// The outcome of `borg prune --list --stats`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PruneStats {
+    /// Number of pruned archives
+    pub pruned: usize,
+    /// Number of kept archives
+    pub kept: usize,
+    /// Deduplicated size of the deleted data in bytes (negative)
+    pub deleted_size: Option<i64>,
+}
+
+impl PruneStats {
+    /// Parse the `lines` of `borg prune --list --stats` e.g. "Pruning archive (1/3): ..." and
+    /// "Deleted data: -1.23 GB -800.00 MB -12.34 MB" with the deduplicated size in the last column
+    fn parse<'l>(lines: impl IntoIterator<Item = &'l str>) -> Self {
+        let mut stats = Self::default();
+        for line in lines {
+            let line = line.trim_start();
+            if line.starts_with("Pruning archive") {
+                stats.pruned += 1;
+            } else if line.starts_with("Keeping archive") {
+                stats.kept += 1;
+            } else if let Some(sizes) = line.strip_prefix("Deleted data:") {
+                let sizes: Vec<&str> = sizes.split_whitespace().collect();
+                if let [.., value, unit] = sizes[..] {
+                    stats.deleted_size = parse_size(value, unit);
+                }
+            }
+        }
+        stats
+    }
+}
+
+/// Parse a size of borg with a decimal or binary `unit` e.g. `-12.34` `MB` or `1.5` `GiB`
+fn parse_size(value: &str, unit: &str) -> Option<i64> {
+    let factor: f64 = match unit {
+        "B" => 1.0,
+        "kB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        "PB" => 1e15,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0_f64.powi(2),
+        "GiB" => 1024.0_f64.powi(3),
+        "TiB" => 1024.0_f64.powi(4),
+        "PiB" => 1024.0_f64.powi(5),
+        _ => return None,
+    };
+    #[allow(clippy::cast_possible_truncation)]
+    value
+        .parse::<f64>()
+        .ok()
+        .map(|value| (value * factor).round() as i64)
+}
+
+impl Output {
+    /// The outcome of `borg prune --list --stats` from its log messages
+    pub fn prune_stats(&self) -> PruneStats {
+        PruneStats::parse(
+            self.messages
+                .iter()
+                .flat_map(|m| m.message.lines())
+                .chain(self.stderr.lines()),
+        )
+    }
+}
+
 /// Wrapper to call the borg binary on OS level
+#[allow(clippy::struct_excessive_bools)]
 pub struct Borg<'a> {
-    bin: &'a PathBuf,
+    bin: &'a Path,
     env: &'a Env,
+    /// BORG_* vars inherited from the environment
+    keep_env: Vec<&'a str>,
+    /// Additional raw `borg info` options
+    info_options: &'a [String],
+    log_json: bool,
+    bypass_lock: bool,
+    /// Order to select the newest archives
+    last_by: cli::LastBy,
+    unknown_unencrypted_repo_access_is_ok: bool,
+    relocated_repo_access_is_ok: bool,
+    /// Policy for modifying options of `borg check` e.g. `--repair`
+    check_repair: cli::CheckRepair,
+    /// Terminate `borg check` after this duration
+    check_timeout: Option<std::time::Duration>,
+    /// Receives the progress messages of `borg check` (requires `log_json`)
+    progress: Option<&'a dyn Fn(&str)>,
 }
 
 impl<'a> From<&'a Repository> for Borg<'a> {
     /// Create new borg instance with a scoped environment
     fn from(repo: &'a Repository) -> Self {
         Borg {
-            bin: &repo.borg_binary,
+            bin: repo.borg_binary.as_deref().unwrap_or(Path::new("borg")),
             env: &repo.env,
+            keep_env: cli::args()
+                .keep_env
+                .as_deref()
+                .map_or(Vec::new(), |vars| vars.split_whitespace().collect()),
+            info_options: &repo.info_options,
+            log_json: repo.log_json,
+            bypass_lock: repo.bypass_lock,
+            last_by: repo.last_by,
+            unknown_unencrypted_repo_access_is_ok: repo.unknown_unencrypted_repo_access_is_ok,
+            relocated_repo_access_is_ok: repo.relocated_repo_access_is_ok,
+            check_repair: repo.check_repair,
+            check_timeout: repo.check_timeout,
+            progress: None,
         }
     }
 }
 
-impl Borg<'_> {
-    /// Execute borg with given arguments and env scope
-    fn exec<I, S>(&self, args: I) -> Result<Output>
+impl<'a> Borg<'a> {
+    /// Run the resolved borg binary `bin` instead of the configured one
+    pub fn with_binary(self, bin: &'a Path) -> Self {
+        Self { bin, ..self }
+    }
+
+    /// Pass the progress messages of `borg check` to the `progress` function (requires `log_json`)
+    pub fn with_progress(self, progress: &'a dyn Fn(&str)) -> Self {
+        Self {
+            progress: Some(progress),
+            ..self
+        }
+    }
+
+    /// The environment of the borg process: The process scope without BORG_* vars (except the
+    /// kept ones) extended by the required defaults and the repository env.
+    pub fn environment(&self) -> std::collections::BTreeMap<OsString, OsString> {
+        // remove systemd NOTIFY_SOCKET as precaution since borgreport emits a status by itself
+        let mut env: std::collections::BTreeMap<OsString, OsString> = std::env::vars_os()
+            .filter(|(k, _)| match k.to_str() {
+                Some(k) => {
+                    (!k.starts_with("BORG_") || self.keep_env.contains(&k)) && k != "NOTIFY_SOCKET"
+                }
+                None => true,
+            })
+            .collect();
+        let defaults = BORG_DEFAULT_ENV
+            .into_iter()
+            .chain(
+                self.unknown_unencrypted_repo_access_is_ok
+                    .then_some(("BORG_UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK", "yes")),
+            )
+            .chain(
+                self.relocated_repo_access_is_ok
+                    .then_some(("BORG_RELOCATED_REPO_ACCESS_IS_OK", "yes")),
+            );
+        env.extend(defaults.map(|(k, v)| (k.into(), v.into())));
+        env.extend(self.env.iter().map(|(k, v)| (k.into(), v.into())));
+        env
+    }
+
+    /// Execute borg with given arguments and env scope.
+    /// Borg is killed and an error returned, when it runs longer than the `timeout`.
+    fn exec<I, S>(&self, args: I, timeout: Option<std::time::Duration>) -> Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.exec_with(args, timeout, &[], false)
+    }
+
+    /// Execute borg like `exec` with the additional `env` vars e.g. a confirmation of a single command.
+    /// The peak memory usage is only measured, when `measure` is set.
+    fn exec_with<I, S>(
+        &self,
+        args: I,
+        timeout: Option<std::time::Duration>,
+        env: &[(&str, &str)],
+        measure: bool,
+    ) -> Result<Output>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        let mut command = std::process::Command::new(self.bin);
-        // Collect all present BORG_* vars and remove them from process scope
-        // remove systemd NOTIFY_SOCKET as precaution since borgreport emits a status by itself
-        std::env::vars_os()
-            .filter_map(|(k, _)| k.into_string().ok())
-            .filter(|k| k.starts_with("BORG_") || k.eq("NOTIFY_SOCKET"))
-            .for_each(|k| {
-                command.env_remove(k);
-            });
         // Run the command and measure the duration
         let now = jiff::Zoned::now();
-        let output = command
-            .envs(BORG_DEFAULT_ENV)
-            .envs(self.env)
+        let child = std::process::Command::new(self.bin)
+            .env_clear()
+            .envs(self.environment())
+            .envs(env.iter().copied())
             .args(BORG_COMMON_ARGS)
+            .args(if self.log_json {
+                &BORG_LOG_JSON_ARGS[..]
+            } else {
+                &[]
+            })
             .args(args)
-            .output()
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
             .context(format!("Failed to execute borg binary: `{:?}`", &self.bin))?;
+        let progress = self.progress.filter(|_| self.log_json);
+        // Measuring the resources requires to wait for the child in `wait_with`
+        let (output, peak_rss) = match (timeout, progress) {
+            (None, None) if !measure => (child.wait_with_output()?, None),
+            (timeout, progress) => wait_with(child, timeout, progress)?,
+        };
+        let peak_rss = peak_rss.filter(|_| measure);
         let duration = jiff::Zoned::now().duration_since(&now);
 
         // Convert output to unicode
-        Ok(Output {
+        let mut output = Output {
             status: output.status,
             stderr: String::from_utf8(output.stderr)
                 .context("Failed to convert borg stderr into an UTF-8 String!")?,
             stdout: String::from_utf8(output.stdout)
                 .context("Failed to convert borg stdout into an UTF-8 String!")?,
+            messages: Vec::new(),
             duration,
-        })
+            peak_rss,
+        };
+        if self.log_json {
+            output.parse_log_json();
+        }
+        Ok(output)
     }
 
-    /// Query borg info command
-    pub fn info(&self, archive_glob: Option<&str>) -> Result<Info> {
-        let mut args = vec!["--bypass-lock", "info"];
+    /// Query a borg `command` with JSON output for a `selection` of archives (e.g. `--last 1`)
+    /// Fails with `Locked` when the lock is respected and the repository is locked.
+    fn query<T>(&self, command: &str, archive_glob: Option<&str>, selection: &[&str]) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let mut args = if self.bypass_lock {
+            vec!["--bypass-lock", command]
+        } else {
+            vec![command]
+        };
         if let Some(glob) = archive_glob {
             args.extend(["--glob-archives", glob]);
         }
-        args.extend(["--last", "1", "--json", "::"]);
+        if self.last_by == cli::LastBy::Name {
+            args.extend(["--sort-by", "name"]);
+        }
+        args.extend(selection);
+        args.extend(["--json", "::"]);
 
-        let output = self.exec(args)?;
+        let output = self.exec(args, None)?;
 
         if output.status.success() {
-            let info = serde_json::from_str(&output.stdout)
-                .context("Failed to parse JSON response of `borg info` command in serde!")?;
-            Ok(info)
+            serde_json::from_str(&output.stdout)
+                .map_err(|e| anyhow!(json_error_message(command, &output.stdout, &e)))
+        } else if output.is_locked() {
+            Err(Locked(output.errors()).into())
+        } else if output.is_unreachable() {
+            Err(Unreachable(output.errors()).into())
+        } else if output.is_not_initialized() {
+            bail!(
+                "Repository {} is not initialized. Create it with `borg init` or check the path.\n{}",
+                self.env.get("BORG_REPO").map_or("", String::as_str),
+                output.errors()
+            );
         } else {
-            bail!(output.stderr);
+            bail!(output.errors());
+        }
+    }
+
+    /// Query borg info command for the `last` newest archives
+    pub fn info(&self, archive_glob: Option<&str>, last: usize) -> Result<Info> {
+        let last = last.to_string();
+        let mut selection = vec!["--last", &last];
+        selection.extend(self.info_options.iter().map(String::as_str));
+        self.query("info", archive_glob, &selection)
+    }
+
+    /// Query borg list command for the oldest archive
+    pub fn list_first(&self, archive_glob: Option<&str>) -> Result<List> {
+        self.query("list", archive_glob, &["--first", "1"])
+    }
+
+    /// Query borg list command for all archives
+    pub fn list(&self, archive_glob: Option<&str>) -> Result<List> {
+        self.query("list", archive_glob, &[])
+    }
+
+    /// Query the storage quota of the repository in bytes via `borg config`. A quota of 0 means no quota.
+    pub fn storage_quota(&self) -> Result<u64> {
+        let output = self.exec(["config", "::", "storage_quota"], None)?;
+        if !output.status.success() {
+            bail!(output.errors());
         }
+        output
+            .stdout
+            .trim()
+            .parse()
+            .context("Failed to parse the storage quota of `borg config`")
     }
 
     /// Check an archive in the repo: `borg check ::<ARCHIVE>` or the whole repo otherwise
@@ -110,11 +722,190 @@ impl Borg<'_> {
         T: AsRef<str>,
     {
         let mut args = vec!["check"];
+        if self.log_json && self.progress.is_some() {
+            args.push("--progress");
+        }
         args.extend(check_opts.iter().map(AsRef::as_ref));
 
         let repository_or_archive = format!("::{}", archive_name.unwrap_or_default());
         args.push(repository_or_archive.as_str());
 
-        self.exec(args)
+        self.exec_with(
+            args,
+            self.check_timeout,
+            check_env(check_opts, self.check_repair),
+            cli::args().show_resources,
+        )
+    }
+
+    /// Prune the archives of the repo matching the `archive_glob`:
+    /// `borg prune --list --stats [--glob-archives <GLOB>] <PRUNE_OPTS> ::`
+    pub fn prune<T>(&self, archive_glob: Option<&str>, prune_opts: &[T]) -> Result<Prune>
+    where
+        T: AsRef<str>,
+    {
+        self.exec(prune_args(archive_glob, prune_opts), None)
+    }
+}
+
+/// The additional env of `borg check`: A modifying option is confirmed only with the policy `allow`.
+/// Otherwise borg asks for a confirmation and aborts the non-interactive run.
+fn check_env<T>(
+    check_opts: &[T],
+    policy: cli::CheckRepair,
+) -> &'static [(&'static str, &'static str)]
+where
+    T: AsRef<str>,
+{
+    let modifying = check_opts
+        .iter()
+        .any(|o| MODIFYING_CHECK_OPTIONS.contains(&o.as_ref()));
+    if modifying && policy == cli::CheckRepair::Allow {
+        &[("BORG_CHECK_I_KNOW_WHAT_I_AM_DOING", "YES")]
+    } else {
+        &[]
+    }
+}
+
+/// The arguments of `borg prune`. The `--keep-*` rules apply only to the archives of the `archive_glob`.
+fn prune_args<'a, T>(archive_glob: Option<&'a str>, prune_opts: &'a [T]) -> Vec<&'a str>
+where
+    T: AsRef<str>,
+{
+    let mut args = vec!["prune", "--list", "--stats"];
+    if let Some(glob) = archive_glob {
+        args.extend(["--glob-archives", glob]);
+    }
+    args.extend(prune_opts.iter().map(AsRef::as_ref));
+    args.push("::");
+    args
+}
+
+mod tests {
+    #[test]
+    fn parse_version() {
+        use super::parse_version;
+        use semver::Version;
+
+        assert_eq!(parse_version("borg 1.2.8\n"), Some(Version::new(1, 2, 8)));
+        assert_eq!(parse_version("borg2 2.0"), Some(Version::new(2, 0, 0)));
+        assert_eq!(
+            parse_version("borg2 2.0.0b14"),
+            Version::parse("2.0.0-b14").ok()
+        );
+        assert_eq!(
+            parse_version("borg 1.4.0rc1"),
+            Version::parse("1.4.0-rc1").ok()
+        );
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_version("borg unknown"), None);
+    }
+
+    #[test]
+    fn termination_message() {
+        use super::termination_message;
+        use signal_hook::consts::signal::{SIGKILL, SIGSEGV};
+
+        assert_eq!(
+            termination_message(SIGKILL),
+            "borg terminated by signal 9 (SIGKILL, possibly OOM)"
+        );
+        assert_eq!(
+            termination_message(SIGSEGV),
+            "borg terminated by signal 11 (SIGSEGV)"
+        );
+    }
+
+    #[test]
+    fn json_error_message() {
+        use super::json_error_message;
+
+        let message = |stdout: &str| {
+            serde_json::from_str::<serde_json::Value>(stdout)
+                .err()
+                .map(|e| json_error_message("info", stdout, &e))
+                .unwrap_or_default()
+        };
+        assert!(message(r#"{"archives": [{"name""#)
+            .starts_with("borg info succeeded but its response is truncated"));
+        assert!(message("Traceback")
+            .starts_with("borg info succeeded but its response is not valid JSON"));
+        assert!(message("").starts_with("borg info succeeded but its response is empty"));
+        assert!(message(&"[".repeat(300)).ends_with("[…"));
+    }
+
+    #[test]
+    fn check_env() {
+        use super::check_env;
+        use crate::cli::CheckRepair;
+
+        let confirmation = [("BORG_CHECK_I_KNOW_WHAT_I_AM_DOING", "YES")];
+        assert_eq!(check_env(&["--repair"], CheckRepair::Allow), confirmation);
+        assert!(check_env(&["--repair"], CheckRepair::Warn).is_empty());
+        assert!(check_env(&["--repair"], CheckRepair::Deny).is_empty());
+        assert!(check_env(&["--verify-data"], CheckRepair::Allow).is_empty());
+    }
+
+    #[test]
+    fn prune_args() {
+        use super::prune_args;
+
+        let options = ["--keep-daily", "7"];
+        assert_eq!(
+            prune_args(None, &options),
+            ["prune", "--list", "--stats", "--keep-daily", "7", "::"]
+        );
+        assert_eq!(
+            prune_args(Some("host1-*"), &options),
+            [
+                "prune",
+                "--list",
+                "--stats",
+                "--glob-archives",
+                "host1-*",
+                "--keep-daily",
+                "7",
+                "::"
+            ]
+        );
+    }
+
+    #[test]
+    fn prune_stats() {
+        use super::PruneStats;
+
+        let output =
+            "Keeping archive (rule: daily #1):       host-2024-08-06   Tue, 2024-08-06 01:00:00
+Pruning archive (1/2):                    host-2024-08-01   Thu, 2024-08-01 01:00:00
+Pruning archive (2/2):                    host-2024-07-31   Wed, 2024-07-31 01:00:00
+------------------------------------------------------------------------------
+                       Original size      Compressed size    Deduplicated size
+Deleted data:               -9.50 GB             -4.20 GB            -12.34 MB
+All archives:               52.10 GB             24.05 GB              2.81 GB";
+        assert_eq!(
+            PruneStats::parse(output.lines()),
+            PruneStats {
+                pruned: 2,
+                kept: 1,
+                deleted_size: Some(-12_340_000),
+            }
+        );
+        assert_eq!(
+            PruneStats::parse(["Deleted data:  0 B  0 B  -1.5 KiB"]).deleted_size,
+            Some(-1536)
+        );
+        assert_eq!(PruneStats::parse(["Deleted data:"]), PruneStats::default());
+    }
+
+    #[test]
+    fn is_borg_binary_name() {
+        use super::is_borg_binary_name;
+
+        assert!(is_borg_binary_name("borg"));
+        assert!(is_borg_binary_name("borg1"));
+        assert!(is_borg_binary_name("borg-1.2"));
+        assert!(!is_borg_binary_name("borgreport"));
+        assert!(!is_borg_binary_name("borgmatic"));
+        assert!(!is_borg_binary_name("borg.bak"));
     }
 }
@@ -8,6 +8,8 @@ use serde::Deserialize;
 pub struct Info {
     pub archives: Vec<Archive>,
     pub cache: Cache,
+    pub encryption: Encryption,
+    pub repository: RepositoryInfo,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -18,6 +20,10 @@ pub struct Archive {
     pub duration: jiff::SignedDuration,
     pub start: jiff::civil::DateTime,
     pub stats: ArchiveStats,
+    #[serde(default)]
+    pub command_line: Vec<String>,
+    #[serde(default)]
+    pub comment: String,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -35,9 +41,27 @@ pub struct Cache {
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct CacheStats {
+    pub total_size: i64,
+    pub unique_size: i64,
     pub unique_csize: i64,
 }
 
+#[derive(Deserialize, Clone, Debug)]
+pub struct RepositoryInfo {
+    pub last_modified: jiff::civil::DateTime,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Encryption {
+    pub mode: String,
+}
+impl Encryption {
+    /// False for the modes `none` and `authenticated*`, which do not encrypt the data
+    pub fn is_encrypted(&self) -> bool {
+        !(self.mode == "none" || self.mode.starts_with("authenticated"))
+    }
+}
+
 // borg duration is provided as a float value
 mod borg_duration {
     use serde::{Deserialize, Deserializer};
@@ -0,0 +1,16 @@
+// SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use serde::Deserialize;
+
+/// Response from of `borg list` command
+#[derive(Deserialize, Clone, Debug)]
+pub struct List {
+    pub archives: Vec<ListArchive>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ListArchive {
+    pub name: String,
+    pub start: jiff::civil::DateTime,
+}
@@ -0,0 +1,68 @@
+// SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use serde::Deserialize;
+
+/// Severity of a borg log message
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Level {
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+/// A single line emitted by `borg --log-json` on stderr
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogLine {
+    LogMessage(LogMessage),
+    /// Progress of an operation with `--progress` e.g. `borg check`
+    ProgressPercent(ProgressPercent),
+    /// Other progress and file status lines are not evaluated
+    #[serde(other)]
+    Other,
+}
+
+/// A `log_message` record of `borg --log-json`
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct LogMessage {
+    pub levelname: Level,
+    pub name: String,
+    pub message: String,
+    /// Message id of a borg exception e.g. `Repository.DoesNotExist`
+    pub msgid: Option<String>,
+}
+
+/// A `progress_percent` record of `borg --log-json --progress`
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ProgressPercent {
+    /// The progress e.g. `Checking segments 42.0%` (missing in the final record)
+    pub message: Option<String>,
+    pub finished: bool,
+}
+
+mod tests {
+    #[test]
+    fn deserialize() {
+        use crate::borg::{Level, LogLine};
+        let j = r#"{"type": "log_message", "time": 1727612384.4, "message": "Repository /tests/test1-noinit does not exist.", "levelname": "ERROR", "name": "borg.archiver", "msgid": "Repository.DoesNotExist"}"#;
+        #[allow(clippy::unwrap_used)]
+        let line = serde_json::from_str::<LogLine>(j).unwrap();
+        assert!(matches!(line, LogLine::LogMessage(m) if m.levelname == Level::Error));
+
+        let j = r#"{"type": "progress_percent", "operation": 1, "msgid": "check.verify_data", "finished": false, "current": 5, "total": 10, "message": "Verifying data 50%"}"#;
+        #[allow(clippy::unwrap_used)]
+        let line = serde_json::from_str::<LogLine>(j).unwrap();
+        assert!(
+            matches!(line, LogLine::ProgressPercent(p) if p.message.as_deref() == Some("Verifying data 50%"))
+        );
+
+        let j = r#"{"type": "file_status", "status": "A", "path": "etc/hosts"}"#;
+        #[allow(clippy::unwrap_used)]
+        let line = serde_json::from_str::<LogLine>(j).unwrap();
+        assert!(matches!(line, LogLine::Other));
+    }
+}
@@ -2,5 +2,9 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 mod info;
+mod list;
+mod log;
 
 pub use info::*;
+pub use list::*;
+pub use log::*;
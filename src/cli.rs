@@ -1,10 +1,11 @@
 // SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use anyhow::Result;
+use anyhow::{ensure, Context, Result};
 use clap::{
     builder::{NonEmptyStringValueParser, Styles},
-    command, value_parser, ArgMatches, Command, CommandFactory, FromArgMatches, Parser, ValueHint,
+    command, value_parser, ArgMatches, Command, CommandFactory, FromArgMatches, Parser, ValueEnum,
+    ValueHint,
 };
 use constcat::concat;
 
@@ -15,48 +16,200 @@ pub(crate) mod args {
     //Clap processes option and ENV
     pub const ENV_DIR: &str = "BORGREPORT_ENV_DIR";
     pub const ENV_INHERIT: &str = "BORGREPORT_ENV_INHERIT";
+    pub const ENV_GROUPS: &str = "BORGREPORT_ENV_GROUPS";
+    pub const KEY_DIR: &str = "BORGREPORT_KEY_DIR";
+    pub const KEY_REPO: &str = "BORGREPORT_KEY_REPO";
+    pub const ENV_FILE: &str = "BORGREPORT_ENV_FILE";
     pub const MAILTOADDR: &str = "BORGREPORT_MAIL_TO";
     pub const MAILFROMADDR: &str = "BORGREPORT_MAIL_FROM";
+    pub const ENVIRONMENT: &str = "BORGREPORT_ENVIRONMENT";
     pub const NOPROGRESS: &str = "BORGREPORT_NO_PROGRESS";
     pub const TEXTFILE: &str = "BORGREPORT_TEXT_TO";
     pub const HTMLFILE: &str = "BORGREPORT_HTML_TO";
+    pub const JSONFILE: &str = "BORGREPORT_JSON_TO";
     pub const METRICSFILE: &str = "BORGREPORT_METRICS_TO";
+    pub const LOGFILE: &str = "BORGREPORT_LOG_TO";
+    pub const METRICS_PREFIX: &str = "BORGREPORT_METRICS_PREFIX";
+    pub const METRICS_MINIMAL: &str = "BORGREPORT_METRICS_MINIMAL";
+    pub const METRICS_FLAT: &str = "BORGREPORT_METRICS_FLAT";
+    pub const FORMAT: &str = "BORGREPORT_FORMAT";
+    pub const STATE_DIR: &str = "BORGREPORT_STATE_DIR";
+    pub const CHECK_OPTIONS_APPEND: &str = "BORGREPORT_CHECK_OPTIONS_APPEND";
+    pub const KEEP_ENV: &str = "BORGREPORT_KEEP_ENV";
+    pub const SHOW_ENCRYPTION: &str = "BORGREPORT_SHOW_ENCRYPTION";
+    pub const SHOW_SAVINGS: &str = "BORGREPORT_SHOW_SAVINGS";
+    pub const SHOW_COMMAND: &str = "BORGREPORT_SHOW_COMMAND";
+    pub const SHOW_COMMENT: &str = "BORGREPORT_SHOW_COMMENT";
+    pub const SHOW_EXIT_CODES: &str = "BORGREPORT_SHOW_EXIT_CODES";
+    pub const SHOW_RESOURCES: &str = "BORGREPORT_SHOW_RESOURCES";
+    pub const SHOW_CHECK_CHANGES: &str = "BORGREPORT_SHOW_CHECK_CHANGES";
+    pub const ERROR_LINES: &str = "BORGREPORT_ERROR_LINES";
+    pub const NO_TRUNCATE: &str = "BORGREPORT_NO_TRUNCATE";
+    pub const SHOW_OK: &str = "BORGREPORT_SHOW_OK";
+    pub const ROLLUP: &str = "BORGREPORT_ROLLUP";
+    pub const EXIT_STATUS: &str = "BORGREPORT_EXIT_STATUS";
+    pub const FAIL_ON_WARNING: &str = "BORGREPORT_FAIL_ON_WARNING";
+    pub const POST_COMMAND: &str = "BORGREPORT_POST_COMMAND";
+    pub const MAX_RUNTIME: &str = "BORGREPORT_MAX_RUNTIME";
+    pub const REQUIRE_REPOS: &str = "BORGREPORT_REQUIRE_REPOS";
+    pub const REDACT: &str = "BORGREPORT_REDACT";
 
     // Clap ignores the ENV (soft override at repository level allowed)
     pub const GLOB_ARCHIVES: &str = "BORGREPORT_GLOB_ARCHIVES";
+    pub const GLOB_LABELS: &str = "BORGREPORT_GLOB_LABELS";
+    pub const GLOB_HOSTNAMES: &str = "BORGREPORT_GLOB_HOSTNAMES";
+    pub const TIERS: &str = "BORGREPORT_TIERS";
+    pub const PRIMARY_GLOB: &str = "BORGREPORT_PRIMARY_GLOB";
+    pub const VIRTUAL_REPOS: &str = "BORGREPORT_VIRTUAL_REPOS";
     pub const CHECK: &str = "BORGREPORT_CHECK";
     pub const CHECK_OPTIONS: &str = "BORGREPORT_CHECK_OPTIONS";
+    pub const CHECK_REPAIR: &str = "BORGREPORT_CHECK_REPAIR";
+    pub const PRUNE: &str = "BORGREPORT_PRUNE";
+    pub const PRUNE_OPTIONS: &str = "BORGREPORT_PRUNE_OPTIONS";
+    pub const CHECK_SCHEDULE: &str = "BORGREPORT_CHECK_SCHEDULE";
+    pub const INFO_OPTIONS: &str = "BORGREPORT_INFO_OPTIONS";
+    pub const CHECK_SAMPLE: &str = "BORGREPORT_CHECK_SAMPLE";
+    pub const CHECK_IGNORE_PATTERNS: &str = "BORGREPORT_CHECK_IGNORE_PATTERNS";
+    pub const CHECK_TIMEOUT: &str = "BORGREPORT_CHECK_TIMEOUT";
+    pub const CHECK_MAX_INTERVAL_HOURS: &str = "BORGREPORT_CHECK_MAX_INTERVAL_HOURS";
     pub const BORG_BINARY: &str = "BORGREPORT_BORG_BINARY";
+    pub const BORG_VERSION_REQ: &str = "BORGREPORT_BORG_VERSION_REQ";
     pub const MAX_AGE_HOURS: &str = "BORGREPORT_MAX_AGE_HOURS";
+    pub const ALLOW_EMPTY: &str = "BORGREPORT_ALLOW_EMPTY";
+    pub const CLOCK_CHECK: &str = "BORGREPORT_CLOCK_CHECK";
+    pub const ROLLBACK_CHECK: &str = "BORGREPORT_ROLLBACK_CHECK";
+    pub const CRITICAL: &str = "BORGREPORT_CRITICAL";
+    pub const SEVERITY: &str = "BORGREPORT_SEVERITY";
+    pub const REPO_TIMEOUT_AS_WARNING: &str = "BORGREPORT_REPO_TIMEOUT_AS_WARNING";
+    pub const LAST_ARCHIVES: &str = "BORGREPORT_LAST_ARCHIVES";
+    pub const LAST_BY: &str = "BORGREPORT_LAST_BY";
+    pub const LOG_JSON: &str = "BORGREPORT_LOG_JSON";
+    pub const MAX_GROWTH_PERCENT: &str = "BORGREPORT_MAX_GROWTH_PERCENT";
+    pub const ANOMALY_SIGMA: &str = "BORGREPORT_ANOMALY_SIGMA";
+    pub const MIN_FREE_BYTES: &str = "BORGREPORT_MIN_FREE_BYTES";
+    pub const MIN_FREE_PERCENT: &str = "BORGREPORT_MIN_FREE_PERCENT";
+    pub const QUOTA_WARN_PERCENT: &str = "BORGREPORT_QUOTA_WARN_PERCENT";
+    pub const STALE_RUNS: &str = "BORGREPORT_STALE_RUNS";
+    pub const MAIL_ON: &str = "BORGREPORT_MAIL_ON";
+    pub const BYPASS_LOCK: &str = "BORGREPORT_BYPASS_LOCK";
+    pub const UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK: &str =
+        "BORGREPORT_UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK";
+    pub const RELOCATED_REPO_ACCESS_IS_OK: &str = "BORGREPORT_RELOCATED_REPO_ACCESS_IS_OK";
+    // Alternative names of the repository options as (alias, option)
+    pub const ALIASES: [(&str, &str); 2] = [
+        ("BORGREPORT_RELOCATED_REPO_OK", RELOCATED_REPO_ACCESS_IS_OK),
+        (
+            "BORGREPORT_UNKNOWN_UNENCRYPTED_REPO_OK",
+            UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK,
+        ),
+    ];
 
     // Not used as env var
     pub const HELP2MAN: &str = "__HELP2MAN";
+    pub const ASK_PASSPHRASE: &str = "__ASK_PASSPHRASE";
+    pub const CONFIG_CHECK: &str = "__CONFIG_CHECK";
+    pub const BORG_ENV: &str = "__BORG_ENV";
+    pub const PRINT_ENV: &str = "__PRINT_ENV";
+    pub const PRINT_JSON_SCHEMA: &str = "__PRINT_JSON_SCHEMA";
+    pub const TEST_MAIL: &str = "__TEST_MAIL";
+    pub const WATCH: &str = "__WATCH";
+    pub const SERVE: &str = "__SERVE";
+    pub const FAST: &str = "__FAST";
+    pub const ONELINE: &str = "__ONELINE";
+    pub const DIGEST: &str = "__DIGEST";
+    pub const ENV_STDIN: &str = "__ENV_STDIN";
 }
 
 pub(crate) mod long_help {
     //Clap processes the ENV
     pub const ENV_DIR: &str =
         "Directory to look for *.env files containing BORG_* env vars for a repository. Each file name represents a repository name in the report.";
+    pub const ENV_FILE: &str = "Load global BORGREPORT_* defaults from <FILE> into the environment. Variables already set in the environment and command line options take precedence. Unlike the *.env files of --env-dir, the <FILE> does not describe a repository.";
     pub const ENV_INHERIT: &str = "Inherit BORG_* env vars for a single <REPOSITORY>. This allows to run `borgreport` after `borg` while reusing the environment.";
+    pub const ENV_GROUPS: &str = "Inherit a repository for each group of indexed env vars <PREFIX>_<INDEX>_<KEY> e.g. REPO_0_BORG_REPO and REPO_0_BORG_PASSPHRASE for \"REPO\". The BORG_* and BORGREPORT_* keys of a group configure its repository. The repository name is taken from <PREFIX>_<INDEX>_NAME or the final path component of its BORG_REPO. This allows to configure many repositories without files e.g. in a container.";
+    pub const KEY_DIR: &str = "Directory to look for *.key files of repositories in keyfile mode. Each file name represents a repository name in the report. The repository is accessed with the key file as BORG_KEY_FILE at the location of --key-repo. A passphrase can be kept from the environment via --keep-env.";
+    pub const KEY_REPO: &str = "Location of the repositories of the --key-dir, where {name} is replaced by the name of the key file e.g. \"ssh://backup@nas/./{name}\".";
     pub const MAILTOADDR: &str =
         "Send the report to <ADDR> using a 'sendmail' compatible mail transfer agent.";
     pub const MAILFROMADDR: &str =
         "The mail sender <ADDR>. By default this is the current user@host";
+    pub const ENVIRONMENT: &str = "Label the report with the environment <NAME> e.g. \"prod\" or \"staging\". The label is shown in the titles, the mail subject, as a banner in the HTML report and as the `environment` label of the `borgreport_info` metric. Names starting with \"prod\" get a prominent banner. (Default: no label)";
     pub const NOPROGRESS: &str =
         "Suppress all status updates during processing. By default this is auto-detected.";
     pub const TEXTFILE: &str = "Write the text report to <FILE> instead of stdout.";
     pub const HTMLFILE: &str = "Write the HTML report to <FILE>.";
+    pub const JSONFILE: &str = "Write the report as JSON to <FILE> for processing by other tools. The document has a top-level `schema_version`, timestamps in ISO 8601, sizes in bytes and durations in seconds. Each record names its `repository` and `archive_glob`.";
     pub const METRICSFILE: &str = "Write metrics to <FILE>.";
+    pub const LOGFILE: &str = "Append a timestamped summary line of each run to <FILE> e.g. `2024-11-12T01:00:00Z status=ok repositories=3 errors=0 warnings=1 size=1.2TB`.";
+    pub const METRICS_PREFIX: &str = "Replace the prefix `borg` of all metric names e.g. \"backup\" emits `backup_*` and `backupreport_*` instead of `borg_*` and `borgreport_*`. The prefix must start with a letter or underscore followed by letters, digits or underscores. (Default: \"borg\")";
+    pub const METRICS_MINIMAL: &str = "Omit the HELP and UNIT lines of the metrics to reduce the size of the file. The TYPE lines are kept. (Default: false)";
+    pub const METRICS_FLAT: &str = "Name the metrics of the repositories without the prefix e.g. repository_unique_csize_bytes instead of borg_repository_unique_csize_bytes. The prefix still applies to the own metrics of borgreport. (Default: false)";
+    pub const FORMAT: &str = "Write a comma separated list of formats e.g. \"text,metrics\" to stdout. Each format is preceded by a delimiter line \"==> <FORMAT> <==\" to split the stream.";
+    pub const ROLLUP: &str = "Add a summary aggregated by <host>: The number of repositories and last archives and the total sizes per hostname of the last archives.";
+    pub const REDACT: &str = "A list of comma separated regular expressions e.g. \"secret-token,nas\\.internal\" to replace each match in all outputs with ***. This is a best-effort safety net for sharing reports and does not replace keeping secrets out of the borg output.";
+    pub const REQUIRE_REPOS: &str = "Fail with an error instead of a warning when no repository is configured, e.g. for an empty env directory.";
+    pub const EXIT_STATUS: &str = "Exit with the aggregated status of all repositories: 0 (OK) without errors, 1 (DEGRADED) when some repositories have errors and 2 (CRITICAL) when all or a critical repository have errors. It cannot be combined with --watch.";
+    pub const FAIL_ON_WARNING: &str = "Treat a warning like an error for the aggregated status (e.g. the exit status) and the mail policy. The report still shows it as warning.";
+    pub const SHOW_OK: &str = "List all healthy repositories in an OK section. A repository is healthy with a last archive, all checks passed and without any warning or error.";
+    pub const ERROR_LINES: &str = "Fold each error and warning message in the text report after <N> lines and note the number of omitted lines. <N> must be at least 1. (Default: unlimited)";
+    pub const NO_TRUNCATE: &str = "Show all lines of each error and warning message in the text report and ignore --error-lines e.g. for a single run to see a full traceback.";
+    pub const SHOW_ENCRYPTION: &str = "Show the encryption mode of each repository in the summary.";
+    pub const SHOW_SAVINGS: &str = "Show the share of the source size saved by deduplication and compression of each repository in the summary and of all repositories in the footer.";
+    pub const SHOW_COMMAND: &str = "Show the command line, which created the archive, in the summary e.g. to verify the backed up paths. Long command lines are truncated.";
+    pub const SHOW_COMMENT: &str = "Show the comment of the archive in the summary e.g. a note like \"pre-upgrade snapshot\". Long comments are truncated.";
+    pub const SHOW_EXIT_CODES: &str = "Show the exit code of `borg check` in the check results to tell a warning (1) from an error (2) or a terminating signal.";
+    pub const SHOW_RESOURCES: &str = "Show the peak memory usage (max RSS) of `borg check` in the check result e.g. to size a host, which runs out of memory. The value is exported as metric in addition. The memory usage is only measured on Linux.";
+    pub const SHOW_CHECK_CHANGES: &str = "Mark a repository in the check results as newly failing or recovered, when the outcome of `borg check` changed since the previous check. The change is exported as metric `borg_check_status_changed_boolean`. Requires a state directory. (Default: false)";
+    pub const CHECK_OPTIONS_APPEND: &str = "A list of space separated raw borg options appended to the `borg check` options of each repository. These follow the repository options and take precedence, when borg evaluates an option twice. Options containing spaces can be given as JSON array of strings.";
+    pub const KEEP_ENV: &str = "A list of space separated BORG_* variables e.g. \"BORG_BASE_DIR BORG_CACHE_DIR\" inherited from the environment by all repositories. All other BORG_* variables are removed from the environment of `borg`. A repository can still set its own value. (Default: \"\")";
+    pub const POST_COMMAND: &str = "Run <CMD> with `sh -c` after all outputs are produced e.g. to upload the report. The environment provides the written report files as BORGREPORT_TEXT_FILE, BORGREPORT_HTML_FILE, BORGREPORT_JSON_FILE and BORGREPORT_METRICS_FILE, the counts as BORGREPORT_ERRORS and BORGREPORT_WARNINGS and the aggregated status as BORGREPORT_STATUS. A failure is shown on stderr.";
+    pub const MAX_RUNTIME: &str = "Stop to process further repositories, when the run exceeds <DURATION> e.g. 30m, 4h or 1d. The report of the processed repositories is produced and each skipped repository is reported with a warning. A running `borg` command is not interrupted.";
+    pub const STATE_DIR: &str = "Directory to keep the state of each repository between runs. Checks comparing with a previous run require a <DIR>. (Default: \"\")";
 
     // Clap ignores the ENV
     pub const GLOB_ARCHIVES: &str =
         "A list of space separated archive globs e.g. \"etc-* srv-*\" for archive names starting with etc- or srv-. (Default: \"\")";
+    pub const GLOB_LABELS: &str = "A list of space separated <GLOB>=<LABEL> pairs e.g. \"etc-*=config srv-*=data\" to use a friendly name as `archive_glob` label in the metrics. (Default: \"\")";
+    pub const GLOB_HOSTNAMES: &str = "A list of space separated <GLOB>=<HOSTNAME> pairs e.g. \"web01-*=web01 db-*=db01\" to warn, when an archive of the glob was created on another host. (Default: \"\")";
+    pub const TIERS: &str = "A list of space separated <TIER>=<GLOB> pairs e.g. \"hourly=db-h-* daily=db-d-*\" to summarize the archives of each glob as a named tier. The globs are processed in addition to the archive globs and the tier is exported as `tier` label in the metrics. (Default: \"\")";
+    pub const PRIMARY_GLOB: &str = "Show only the archives of the archive <GLOB> in the summary. The other archive globs are still processed for checks, warnings and metrics. The <GLOB> must be one of the archive globs or tiers. (Default: \"\")";
+    pub const VIRTUAL_REPOS: &str = "A list of space separated <NAME>=<GLOB> pairs e.g. \"web=web-* db=db-*\" to report the archives of each glob as virtual repository <REPO>/<NAME>, e.g. for many hosts sharing one repository. Each virtual repository replaces the archive globs and has its own errors, warnings, state and metrics. (Default: \"\")";
     pub const CHECK: &str = "Enables the execution of `borg check`. (Default: false)";
+    pub const CHECK_SCHEDULE: &str = "A list of space separated weekdays and days of the month e.g. \"Sun 1\" to run `borg check` only on Sundays and on the first day of a month. On other days the skipped check is noted in the report. (Default: \"\")";
     pub const CHECK_OPTIONS: &str =
-        "A list of space separated raw borg options supplied to the `borg check` command";
+        "A list of space separated raw borg options supplied to the `borg check` command. Options containing spaces can be given as JSON array of strings e.g. '[\"--glob-archives\", \"my archive-*\"]'.";
+    pub const CHECK_REPAIR: &str = "Policy for the modifying option --repair in the `borg check` options: <deny> removes the option with a warning, <warn> keeps it with a warning and <allow> keeps it silently. Only with <allow> the option is confirmed to `borg` with BORG_CHECK_I_KNOW_WHAT_I_AM_DOING=YES, otherwise `borg` asks for a confirmation and aborts. This protects from running a repair by a pasted option. (Default: deny)";
+    pub const PRUNE: &str = "Enables the execution of `borg prune --list --stats` after the checks. The prune is skipped, when the repository has errors or warnings. (Default: false)";
+    pub const PRUNE_OPTIONS: &str = "A list of space separated raw borg options supplied to the `borg prune` command e.g. \"--keep-daily 7 --keep-weekly 4\". At least one --keep-* rule is required. With archive globs, each glob is pruned on its own. Options containing spaces can be given as JSON array of strings. (Default: \"\")";
+    pub const INFO_OPTIONS: &str = "A list of space separated raw borg options or a JSON array of strings supplied to the `borg info` command e.g. \"--consider-checkpoints\". The options --json, --last and --first are set by borgreport and cannot be used. (Default: \"\")";
+    pub const CHECK_MAX_INTERVAL_HOURS: &str = "Threshold to warn, when the last successful `borg check` is older than <HOURS>. This is evaluated also on runs without `borg check`. Requires a state directory. (Default: \"\")";
+    pub const CHECK_SAMPLE: &str = "Check <N> random archives instead of the last archive. Archives not checked in the current cycle are preferred, when a state directory is given. (Default: \"\")";
+    pub const CHECK_IGNORE_PATTERNS: &str = "A list of space separated regular expressions. Output lines of `borg check` matching any <PATTERN> are ignored e.g. benign informational messages. Use \\s to match a space. (Default: \"\")";
+    pub const CHECK_TIMEOUT: &str = "Terminate `borg check` after <INTERVAL> e.g. 30m, 6h or 1d and report an error. This applies only to `borg check`. (Default: \"\")";
     pub const BORG_BINARY: &str = "Path to a local 'borg' binary. (Default: borg)";
+    pub const BORG_VERSION_REQ: &str = "A version requirement e.g. \">=1.2, <2\" for the 'borg' binary. Without an explicit binary path, the first binary named borg, borg1, borg2, ... on the PATH satisfying the requirement is used. (Default: \"\")";
     pub const MAX_AGE_HOURS: &str =
         "Threshold to warn, when the last backup is older than <HOURS>. (Default: 24)";
+    pub const LAST_ARCHIVES: &str = "Show the <N> newest archives per archive glob in the summary including the change of the source size since the previous archive. Only the newest archive is checked. (Default: 1)";
+    pub const LAST_BY: &str = "Select the newest archives by their creation <time> or by their <name> e.g. for archive names with timestamps, when the creation time is unreliable after an import. (Default: time)";
+    pub const CLOCK_CHECK: &str = "Warn when archives share the same start time or started before their predecessor, which indicates a stuck or wrong clock on the backup source. The order is not checked with BORGREPORT_LAST_BY=name. Use with BORGREPORT_LAST_ARCHIVES to check more than the last archive. (Default: false)";
+    pub const ROLLBACK_CHECK: &str = "Raise an error, when the last modification time of the repository manifest moved backward since the previous run. This can indicate a rollback or a replaced repository. Requires a state directory. (Default: false)";
+    pub const ALLOW_EMPTY: &str =
+        "Do not warn about an empty repository e.g. when it is freshly provisioned. (Default: false)";
+    pub const REPO_TIMEOUT_AS_WARNING: &str = "Report a connection failure or a timeout of `borg` as warning instead of an error e.g. for a remote repository over a flaky link. (Default: false)";
+    pub const CRITICAL: &str = "Mark the repository as critical. An error in a critical repository sets the aggregated status to CRITICAL. (Default: false)";
+    pub const SEVERITY: &str = "Cap the severity of the repository in the aggregated status, the exit status and the mail policy at <ignore|warning|error> e.g. for a scratch repository. The report still shows all errors and warnings. With \"warning\" an error counts as warning and with \"ignore\" the repository never fails. (Default: error)";
+    pub const MAX_GROWTH_PERCENT: &str = "Threshold to warn, when the repository size grew by more than <PERCENT> since the previous run. Requires a state directory. (Default: \"\")";
+    pub const ANOMALY_SIGMA: &str = "Threshold to warn, when the repository size deviates by more than <N> standard deviations from its moving average of the previous runs. This detects a sudden growth or shrinkage without a fixed threshold. Requires a state directory with at least 5 runs. (Default: \"\")";
+    pub const MIN_FREE_BYTES: &str = "Threshold to warn, when the filesystem of a local repository has less than <BYTES> available. (Default: \"\")";
+    pub const MIN_FREE_PERCENT: &str = "Threshold to warn, when the filesystem of a local repository has less than <PERCENT> available. (Default: \"\")";
+    pub const QUOTA_WARN_PERCENT: &str = "Threshold to warn, when the repository uses more than <PERCENT> of its storage quota. The quota is queried via `borg config` and repositories without a quota are skipped. This is skipped with --fast. (Default: \"\")";
+    pub const STALE_RUNS: &str = "Threshold to warn, when the repository was not modified in the last <N> runs e.g. it became read-only or the client stopped pushing backups. Requires a state directory. (Default: \"\")";
+    pub const MAIL_ON: &str = "Send the report per mail only, when the repository has <always|warning|error>. A mail is sent, when any repository requires it. Messages not related to a repository are evaluated with the global policy. (Default: always)";
+    pub const BYPASS_LOCK: &str = "Run `borg info` with `--bypass-lock`. When disabled, a locked repository is reported as warning. (Default: true)";
+    pub const UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK: &str = "Run `borg` with BORG_UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK=yes to access a previously unknown unencrypted repository without a confirmation prompt. (Default: false)";
+    pub const RELOCATED_REPO_ACCESS_IS_OK: &str = "Run `borg` with BORG_RELOCATED_REPO_ACCESS_IS_OK=yes to access a repository, which was previously located at another path, without a confirmation prompt. Otherwise `borg` aborts and the summary of the repository is empty. (Default: false)";
+    pub const LOG_JSON: &str = "Run `borg` with `--log-json` and classify the structured log messages as warnings or errors by their log level. (Default: false)";
 }
 
 /// Additional --help-man output for generating a manpage with help2man
@@ -64,20 +217,85 @@ pub const HELP2MAN: &str = concat!("Environment:
 Environment variables are overwritten by the respective command line option.
   ",args::ENV_DIR," <DIR>  ", long_help::ENV_DIR,"
   ",args::ENV_INHERIT," <REPOSITORY>  ", long_help::ENV_INHERIT,"
+  ",args::ENV_GROUPS," <PREFIX>  ", long_help::ENV_GROUPS,"
+  ",args::KEY_DIR," <DIR>  ", long_help::KEY_DIR,"
+  ",args::KEY_REPO," <LOCATION>  ", long_help::KEY_REPO,"
+  ",args::ENV_FILE," <FILE>  ", long_help::ENV_FILE,"
   ",args::MAILTOADDR," <ADDR>  ", long_help::MAILTOADDR,"
   ",args::MAILFROMADDR," <ADDR>  ", long_help::MAILFROMADDR,"
+  ",args::ENVIRONMENT," <NAME>  ", long_help::ENVIRONMENT,"
   ",args::NOPROGRESS," <ADDR>  ", long_help::NOPROGRESS,"
   ",args::TEXTFILE," <FILE>  ", long_help::TEXTFILE,"
   ",args::HTMLFILE," <FORMAT>  ", long_help::HTMLFILE,"
+  ",args::JSONFILE," <FILE>  ", long_help::JSONFILE,"
   ",args::METRICSFILE," <FILE>  ", long_help::METRICSFILE,"
+  ",args::LOGFILE," <FILE>  ", long_help::LOGFILE,"
+  ",args::METRICS_PREFIX," <PREFIX>  ", long_help::METRICS_PREFIX,"
+  ",args::METRICS_MINIMAL," <true|false>  ", long_help::METRICS_MINIMAL,"
+  ",args::METRICS_FLAT," <true|false>  ", long_help::METRICS_FLAT,"
+  ",args::FORMAT," <FORMAT,...>  ", long_help::FORMAT,"
+  ",args::STATE_DIR," <DIR>  ", long_help::STATE_DIR,"
+  ",args::CHECK_OPTIONS_APPEND," <OPTS>  ", long_help::CHECK_OPTIONS_APPEND,"
+  ",args::KEEP_ENV," <VARS>  ", long_help::KEEP_ENV,"
+  ",args::SHOW_ENCRYPTION," <true|false>  ", long_help::SHOW_ENCRYPTION,"
+  ",args::SHOW_SAVINGS," <true|false>  ", long_help::SHOW_SAVINGS,"
+  ",args::SHOW_COMMAND," <true|false>  ", long_help::SHOW_COMMAND,"
+  ",args::SHOW_COMMENT," <true|false>  ", long_help::SHOW_COMMENT,"
+  ",args::SHOW_EXIT_CODES," <true|false>  ", long_help::SHOW_EXIT_CODES,"
+  ",args::SHOW_RESOURCES," <true|false>  ", long_help::SHOW_RESOURCES,"
+  ",args::SHOW_CHECK_CHANGES," <true|false>  ", long_help::SHOW_CHECK_CHANGES,"
+  ",args::ERROR_LINES," <N>  ", long_help::ERROR_LINES,"
+  ",args::NO_TRUNCATE," <true|false>  ", long_help::NO_TRUNCATE,"
+  ",args::SHOW_OK," <true|false>  ", long_help::SHOW_OK,"
+  ",args::ROLLUP," <host>  ", long_help::ROLLUP,"
+  ",args::EXIT_STATUS," <true|false>  ", long_help::EXIT_STATUS,"
+  ",args::FAIL_ON_WARNING," <true|false>  ", long_help::FAIL_ON_WARNING,"
+  ",args::POST_COMMAND," <CMD>  ", long_help::POST_COMMAND,"
+  ",args::MAX_RUNTIME," <DURATION>  ", long_help::MAX_RUNTIME,"
+  ",args::REQUIRE_REPOS," <true|false>  ", long_help::REQUIRE_REPOS,"
+  ",args::REDACT," <PATTERNS>  ", long_help::REDACT,"
 
 Repository Environment:
   !  You probably want to configure the following variables at repository level. Setting them globally will alter the default behavior for all repositories.
   ",args::GLOB_ARCHIVES," <GLOB>  ", long_help::GLOB_ARCHIVES,"
+  ",args::GLOB_LABELS," <GLOB=LABEL>  ", long_help::GLOB_LABELS,"
+  ",args::GLOB_HOSTNAMES," <GLOB=HOSTNAME>  ", long_help::GLOB_HOSTNAMES,"
+  ",args::TIERS," <TIER=GLOB>  ", long_help::TIERS,"
+  ",args::PRIMARY_GLOB," <GLOB>  ", long_help::PRIMARY_GLOB,"
+  ",args::VIRTUAL_REPOS," <NAME=GLOB>  ", long_help::VIRTUAL_REPOS,"
   ",args::CHECK," <true|false>  ", long_help::CHECK,"
+  ",args::CHECK_SCHEDULE," <DAYS>  ", long_help::CHECK_SCHEDULE,"
   ",args::CHECK_OPTIONS," <OPTS>  ", long_help::CHECK_OPTIONS,"
+  ",args::CHECK_REPAIR," <deny|warn|allow>  ", long_help::CHECK_REPAIR,"
+  ",args::PRUNE," <true|false>  ", long_help::PRUNE,"
+  ",args::PRUNE_OPTIONS," <OPTS>  ", long_help::PRUNE_OPTIONS,"
+  ",args::INFO_OPTIONS," <OPTS>  ", long_help::INFO_OPTIONS,"
+  ",args::CHECK_SAMPLE," <N>  ", long_help::CHECK_SAMPLE,"
+  ",args::CHECK_IGNORE_PATTERNS," <PATTERN>  ", long_help::CHECK_IGNORE_PATTERNS,"
+  ",args::CHECK_TIMEOUT," <INTERVAL>  ", long_help::CHECK_TIMEOUT,"
+  ",args::CHECK_MAX_INTERVAL_HOURS," <HOURS>  ", long_help::CHECK_MAX_INTERVAL_HOURS,"
   ",args::BORG_BINARY," <FILE>  ", long_help::BORG_BINARY,"
+  ",args::BORG_VERSION_REQ," <REQ>  ", long_help::BORG_VERSION_REQ,"
   ",args::MAX_AGE_HOURS," <HOURS>  ", long_help::MAX_AGE_HOURS,"
+  ",args::ALLOW_EMPTY," <true|false>  ", long_help::ALLOW_EMPTY,"
+  ",args::CLOCK_CHECK," <true|false>  ", long_help::CLOCK_CHECK,"
+  ",args::ROLLBACK_CHECK," <true|false>  ", long_help::ROLLBACK_CHECK,"
+  ",args::CRITICAL," <true|false>  ", long_help::CRITICAL,"
+  ",args::SEVERITY," <ignore|warning|error>  ", long_help::SEVERITY,"
+  ",args::REPO_TIMEOUT_AS_WARNING," <true|false>  ", long_help::REPO_TIMEOUT_AS_WARNING,"
+  ",args::LAST_ARCHIVES," <N>  ", long_help::LAST_ARCHIVES,"
+  ",args::LAST_BY," <time|name>  ", long_help::LAST_BY,"
+  ",args::LOG_JSON," <true|false>  ", long_help::LOG_JSON,"
+  ",args::MAX_GROWTH_PERCENT," <PERCENT>  ", long_help::MAX_GROWTH_PERCENT,"
+  ",args::ANOMALY_SIGMA," <N>  ", long_help::ANOMALY_SIGMA,"
+  ",args::MIN_FREE_BYTES," <BYTES>  ", long_help::MIN_FREE_BYTES,"
+  ",args::MIN_FREE_PERCENT," <PERCENT>  ", long_help::MIN_FREE_PERCENT,"
+  ",args::QUOTA_WARN_PERCENT," <PERCENT>  ", long_help::QUOTA_WARN_PERCENT,"
+  ",args::STALE_RUNS," <N>  ", long_help::STALE_RUNS,"
+  ",args::MAIL_ON," <always|warning|error>  ", long_help::MAIL_ON,"
+  ",args::BYPASS_LOCK," <true|false>  ", long_help::BYPASS_LOCK,"
+  ",args::UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK," <true|false>  ", long_help::UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK,"
+  ",args::RELOCATED_REPO_ACCESS_IS_OK," <true|false>  ", long_help::RELOCATED_REPO_ACCESS_IS_OK,"
 
 Report bugs to <https://github.com/bbx0/borgreport/issues>."
 );
@@ -109,7 +327,38 @@ Written by ",
 static MATCHES: std::sync::OnceLock<ArgMatches> = std::sync::OnceLock::new();
 /// Accessor function to command line arguments.
 pub(crate) fn matches() -> &'static ArgMatches {
-    MATCHES.get_or_init(|| command().get_matches())
+    MATCHES.get_or_init(|| {
+        let matches = command().get_matches();
+        // Load the global defaults and parse again, so the clap env mappings pick them up.
+        match matches.get_one::<std::path::PathBuf>(args::ENV_FILE) {
+            Some(file) => {
+                load_env_file(file).unwrap_or_else(|e| {
+                    eprintln!("Error: {e:#}");
+                    std::process::exit(1)
+                });
+                command().get_matches()
+            }
+            None => matches,
+        }
+    })
+}
+
+/// Load the BORGREPORT_* variables of the env `file` into the process environment.
+/// Variables already present in the environment are not overwritten.
+fn load_env_file(file: &std::path::Path) -> Result<()> {
+    let vars = dotenvy::from_path_iter(file)
+        .and_then(Iterator::collect::<Result<Vec<(String, String)>, _>>)
+        .with_context(|| format!("Cannot parse env file {file:?}"))?;
+    for (key, value) in vars {
+        ensure!(
+            key.starts_with("BORGREPORT_"),
+            "Cannot load env file {file:?}: Only BORGREPORT_* variables are allowed, found {key}"
+        );
+        if std::env::var_os(&key).is_none() {
+            std::env::set_var(key, value);
+        }
+    }
+    Ok(())
 }
 
 /// Structured access to the `ArgMatches`
@@ -130,7 +379,149 @@ pub(crate) fn command() -> Command {
     Args::command()
 }
 
+/// Policy when to send the report per mail
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum MailOn {
+    /// Always send the report
+    #[default]
+    Always,
+    /// Send the report on warnings or errors
+    Warning,
+    /// Send the report on errors
+    Error,
+}
+
+/// Policy for modifying options of `borg check` e.g. `--repair`
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum CheckRepair {
+    /// Remove the option with a warning
+    #[default]
+    Deny,
+    /// Keep the option with a warning
+    Warn,
+    /// Keep the option
+    Allow,
+}
+
+/// Maximum severity of the issues of a repository in the aggregated status
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Severity {
+    /// The repository never fails
+    Ignore,
+    /// An error counts as warning
+    Warning,
+    /// Errors and warnings count as they are
+    #[default]
+    Error,
+}
+
+/// Order to select the newest archives
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum LastBy {
+    /// Select by the creation time
+    #[default]
+    Time,
+    /// Select by the archive name
+    Name,
+}
+
+/// Parse an interval of whole seconds, minutes, hours or days e.g. `90s`, `30m`, `1h` or `1d`
+pub(crate) fn parse_interval(value: &str) -> Result<std::time::Duration, String> {
+    let number = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let secs_per_unit = match &value[number.len()..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err("expected a number with the unit s, m, h or d e.g. 1h".to_string()),
+    };
+    match number.parse::<u64>() {
+        Ok(n) if n > 0 => n
+            .checked_mul(secs_per_unit)
+            .map(std::time::Duration::from_secs)
+            .ok_or_else(|| "the interval is too large".to_string()),
+        _ => Err("expected a positive number e.g. 1h".to_string()),
+    }
+}
+
+/// Parse a BORG_* variable of the form `KEY=VALUE` e.g. `BORG_RSH=ssh -p 2222`
+fn parse_borg_env(value: &str) -> Result<(String, String), String> {
+    let (key, value) = value
+        .split_once('=')
+        .ok_or_else(|| "expected KEY=VALUE e.g. BORG_RSH='ssh -p 2222'".to_string())?;
+    if key.starts_with("BORG_") {
+        Ok((key.to_string(), value.to_string()))
+    } else {
+        Err(format!("only BORG_* variables are allowed, found {key}"))
+    }
+}
+
+/// Parse a prefix of Prometheus metric names e.g. `borg`
+fn parse_metrics_prefix(value: &str) -> Result<String, String> {
+    let mut chars = value.chars();
+    if chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        Ok(value.to_string())
+    } else {
+        Err(
+            "expected letters, digits or underscores not starting with a digit e.g. borg"
+                .to_string(),
+        )
+    }
+}
+
+/// Output formats of the report
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    /// Text report (text/plain)
+    Text,
+    /// HTML report (text/html)
+    Html,
+    /// Metrics (application/openmetrics-text)
+    Metrics,
+    /// JSON report (application/json)
+    Json,
+}
+
+/// Aggregation of the summary
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Rollup {
+    /// Aggregate by the hostname of the last archives
+    Host,
+}
+
+/// Period summarized by a digest
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Digest {
+    /// The last 7 days
+    Weekly,
+    /// The last 30 days
+    Monthly,
+}
+impl Digest {
+    /// Length of the period
+    pub(crate) fn period(self) -> std::time::Duration {
+        let days = match self {
+            Self::Weekly => 7,
+            Self::Monthly => 30,
+        };
+        std::time::Duration::from_secs(days * 24 * 60 * 60)
+    }
+
+    /// Name of the digest
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Weekly => "weekly",
+            Self::Monthly => "monthly",
+        }
+    }
+}
+
 /// Command line interface
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Parser, Debug, Clone)]
 #[command(
     about,
@@ -155,6 +546,62 @@ pub(crate) struct Args {
     )]
     pub(crate) env_dirs: Vec<std::path::PathBuf>,
 
+    #[arg(
+        action = clap::ArgAction::Append,
+        env = args::KEY_DIR,
+        help = "Directory to look for *.key files of repositories in keyfile mode.",
+        hide_env = true,
+        id = args::KEY_DIR,
+        long = "key-dir",
+        long_help = long_help::KEY_DIR,
+        requires = args::KEY_REPO,
+        value_hint = ValueHint::DirPath,
+        value_name = "DIR",
+        value_parser = value_parser!(std::path::PathBuf),
+    )]
+    pub(crate) key_dirs: Vec<std::path::PathBuf>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::KEY_REPO,
+        help = "Location of the repositories of the --key-dir with a {name} placeholder.",
+        hide_env = true,
+        id = args::KEY_REPO,
+        long = "key-repo",
+        long_help = long_help::KEY_REPO,
+        requires = args::KEY_DIR,
+        value_hint = ValueHint::Other,
+        value_name = "LOCATION",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) key_repo: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Append,
+        help = "Pass the BORG_* variable <KEY=VALUE> to every repository.",
+        id = args::BORG_ENV,
+        long = "borg-env",
+        long_help = "Pass the BORG_* variable <KEY=VALUE> to every repository e.g. --borg-env BORG_RSH='ssh -p 2222'. Can be given multiple times. A variable of the repository configuration takes precedence.",
+        value_hint = ValueHint::Other,
+        value_name = "KEY=VALUE",
+        value_parser = parse_borg_env,
+    )]
+    pub(crate) borg_env: Vec<(String, String)>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::ENV_FILE,
+        help = "Load global BORGREPORT_* defaults from <FILE>.",
+        hide_env = true,
+        id = args::ENV_FILE,
+        long = "env-file",
+        long_help = long_help::ENV_FILE,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        value_parser = value_parser!(std::path::PathBuf),
+    )]
+    pub(crate) env_file: Option<std::path::PathBuf>,
+
     #[arg(
         action = clap::ArgAction::Set,
         env = args::ENV_INHERIT,
@@ -169,6 +616,20 @@ pub(crate) struct Args {
     )]
     pub(crate) env_inherit: Option<String>,
 
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::ENV_GROUPS,
+        hide_env = true,
+        help = "Inherit a repository for each group of indexed env vars <PREFIX>_<INDEX>_<KEY>.",
+        long_help = long_help::ENV_GROUPS,
+        id = args::ENV_GROUPS,
+        long = "env-groups",
+        value_hint = ValueHint::Other,
+        value_name = "PREFIX",
+        value_parser = NonEmptyStringValueParser::new(),
+    )]
+    pub(crate) env_groups: Option<String>,
+
     #[arg(
         action = clap::ArgAction::Set,
         env = args::TEXTFILE,
@@ -197,6 +658,20 @@ pub(crate) struct Args {
     )]
     pub(crate) html_file: Option<std::path::PathBuf>,
 
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::JSONFILE,
+        help = long_help::JSONFILE,
+        hide_env = true,
+        id = args::JSONFILE,
+        long = "json-to",
+        long_help = long_help::JSONFILE,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        value_parser = value_parser!(std::path::PathBuf),
+    )]
+    pub(crate) json_file: Option<std::path::PathBuf>,
+
     #[arg(
         action = clap::ArgAction::Set,
         env = args::METRICSFILE,
@@ -211,6 +686,86 @@ pub(crate) struct Args {
     )]
     pub(crate) metrics_file: Option<std::path::PathBuf>,
 
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::LOGFILE,
+        help = "Append a summary line of each run to <FILE>.",
+        hide_env = true,
+        id = args::LOGFILE,
+        long = "log-to",
+        long_help = long_help::LOGFILE,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        value_parser = value_parser!(std::path::PathBuf),
+    )]
+    pub(crate) log_file: Option<std::path::PathBuf>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_value = "borg",
+        env = args::METRICS_PREFIX,
+        help = "Replace the prefix `borg` of all metric names.",
+        hide_env = true,
+        id = args::METRICS_PREFIX,
+        long = "metrics-prefix",
+        long_help = long_help::METRICS_PREFIX,
+        value_hint = ValueHint::Other,
+        value_name = "PREFIX",
+        value_parser = parse_metrics_prefix,
+    )]
+    pub(crate) metrics_prefix: String,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::METRICS_MINIMAL,
+        hide_env = true,
+        help = "Omit the HELP and UNIT lines of the metrics.",
+        long_help = long_help::METRICS_MINIMAL,
+        id = args::METRICS_MINIMAL,
+        long = "metrics-minimal",
+    )]
+    pub(crate) metrics_minimal: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::METRICS_FLAT,
+        hide_env = true,
+        help = "Name the metrics of the repositories without the prefix.",
+        long_help = long_help::METRICS_FLAT,
+        id = args::METRICS_FLAT,
+        long = "metrics-flat",
+    )]
+    pub(crate) metrics_flat: bool,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::FORMAT,
+        hide_env = true,
+        help = "Write the comma separated <FORMAT>s to stdout with a delimiter line",
+        long_help = long_help::FORMAT,
+        id = args::FORMAT,
+        long = "format",
+        value_delimiter = ',',
+        value_hint = ValueHint::Other,
+        value_name = "FORMAT",
+        value_parser = value_parser!(OutputFormat),
+    )]
+    pub(crate) formats: Vec<OutputFormat>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::STATE_DIR,
+        help = "Directory to keep the state of each repository between runs.",
+        hide_env = true,
+        id = args::STATE_DIR,
+        long = "state-dir",
+        long_help = long_help::STATE_DIR,
+        value_hint = ValueHint::DirPath,
+        value_name = "DIR",
+        value_parser = value_parser!(std::path::PathBuf),
+    )]
+    pub(crate) state_dir: Option<std::path::PathBuf>,
+
     #[arg(
         action = clap::ArgAction::Set,
         env = args::MAILTOADDR,
@@ -240,6 +795,20 @@ pub(crate) struct Args {
     )]
     pub(crate) mail_from: Option<lettre::Address>,
 
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::ENVIRONMENT,
+        help = "Label the report with the environment <NAME> e.g. \"prod\".",
+        hide_env = true,
+        id = args::ENVIRONMENT,
+        long = "environment",
+        long_help = long_help::ENVIRONMENT,
+        value_hint = ValueHint::Other,
+        value_name = "NAME",
+        value_parser = NonEmptyStringValueParser::new(),
+    )]
+    pub(crate) environment: Option<String>,
+
     #[arg(
         action = clap::ArgAction::SetTrue,
         env = args::NOPROGRESS,
@@ -251,83 +820,936 @@ pub(crate) struct Args {
     )]
     pub(crate) no_progress: bool,
 
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::SHOW_ENCRYPTION,
+        hide_env = true,
+        help = long_help::SHOW_ENCRYPTION,
+        long_help = long_help::SHOW_ENCRYPTION,
+        id = args::SHOW_ENCRYPTION,
+        long = "show-encryption",
+    )]
+    pub(crate) show_encryption: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::SHOW_SAVINGS,
+        hide_env = true,
+        help = "Show the share of the source size saved in the summary.",
+        long_help = long_help::SHOW_SAVINGS,
+        id = args::SHOW_SAVINGS,
+        long = "show-savings",
+    )]
+    pub(crate) show_savings: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::SHOW_COMMAND,
+        hide_env = true,
+        help = "Show the command line, which created the archive, in the summary.",
+        long_help = long_help::SHOW_COMMAND,
+        id = args::SHOW_COMMAND,
+        long = "show-command",
+    )]
+    pub(crate) show_command: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::SHOW_COMMENT,
+        hide_env = true,
+        help = "Show the comment of the archive in the summary.",
+        long_help = long_help::SHOW_COMMENT,
+        id = args::SHOW_COMMENT,
+        long = "show-comment",
+    )]
+    pub(crate) show_comment: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::SHOW_EXIT_CODES,
+        hide_env = true,
+        help = "Show the exit code of `borg check` in the check results.",
+        long_help = long_help::SHOW_EXIT_CODES,
+        id = args::SHOW_EXIT_CODES,
+        long = "show-exit-codes",
+    )]
+    pub(crate) show_exit_codes: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::SHOW_RESOURCES,
+        hide_env = true,
+        help = "Show the peak memory usage of `borg check` in the check result.",
+        long_help = long_help::SHOW_RESOURCES,
+        id = args::SHOW_RESOURCES,
+        long = "show-resources",
+    )]
+    pub(crate) show_resources: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::SHOW_CHECK_CHANGES,
+        hide_env = true,
+        help = "Mark newly failing and recovered checks in the check results.",
+        long_help = long_help::SHOW_CHECK_CHANGES,
+        id = args::SHOW_CHECK_CHANGES,
+        long = "show-check-changes",
+    )]
+    pub(crate) show_check_changes: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::SHOW_OK,
+        hide_env = true,
+        help = "List all healthy repositories in an OK section.",
+        long_help = long_help::SHOW_OK,
+        id = args::SHOW_OK,
+        long = "show-ok",
+    )]
+    pub(crate) show_ok: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        conflicts_with = args::WATCH,
+        env = args::EXIT_STATUS,
+        hide_env = true,
+        help = "Exit with the aggregated status 0 (OK), 1 (DEGRADED) or 2 (CRITICAL).",
+        long_help = long_help::EXIT_STATUS,
+        id = args::EXIT_STATUS,
+        long = "exit-status",
+    )]
+    pub(crate) exit_status: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::FAIL_ON_WARNING,
+        hide_env = true,
+        help = "Treat a warning like an error for the aggregated status and the mail policy.",
+        long_help = long_help::FAIL_ON_WARNING,
+        id = args::FAIL_ON_WARNING,
+        long = "fail-on-warning",
+    )]
+    pub(crate) fail_on_warning: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::REQUIRE_REPOS,
+        hide_env = true,
+        help = "Fail when no repository is configured.",
+        long_help = long_help::REQUIRE_REPOS,
+        id = args::REQUIRE_REPOS,
+        long = "require-repos",
+    )]
+    pub(crate) require_repos: bool,
+
     #[arg(
         action = clap::ArgAction::Set,
-        help = "Enforce a glob archives filter for all repositories.",
-        help_heading = "Override repository options",
-        id = args::GLOB_ARCHIVES,
-        long = "glob-archives",
-        long_help = long_help::GLOB_ARCHIVES,
-        value_hint = ValueHint::Other,
-        value_name = "GLOB",
+        env = args::POST_COMMAND,
+        hide_env = true,
+        help = "Run <CMD> after all outputs are produced e.g. to upload the report.",
+        long_help = long_help::POST_COMMAND,
+        id = args::POST_COMMAND,
+        long = "post-command",
+        value_hint = ValueHint::CommandString,
+        value_name = "CMD",
         value_parser = value_parser!(String),
     )]
-    pub(crate) glob_archives: Option<String>,
+    pub(crate) post_command: Option<String>,
 
-    // Note: `ArgAction::SetTrue` will cause `Arg::default_value` = `false` but we need `None` when the flag is not present. -> use default_missing_value
     #[arg(
         action = clap::ArgAction::Set,
-        default_missing_value = "true",
-        help = "Enforce to run (or not run) `borg check`",
-        help_heading = "Override repository options",
-        id = args::CHECK,
-        long = "check",
-        long_help = long_help::CHECK,
-        num_args = 0..=1,
-        require_equals = true,
-        hide_possible_values = true,
+        env = args::MAX_RUNTIME,
+        hide_env = true,
+        help = "Stop to process further repositories, when the run exceeds <DURATION> e.g. 4h.",
+        long_help = long_help::MAX_RUNTIME,
+        id = args::MAX_RUNTIME,
+        long = "max-runtime",
         value_hint = ValueHint::Other,
-        value_name = "true|false",
-        value_parser = value_parser!(bool),
+        value_name = "DURATION",
+        value_parser = parse_interval,
     )]
-    pub(crate) check: Option<bool>,
+    pub(crate) max_runtime: Option<std::time::Duration>,
 
     #[arg(
         action = clap::ArgAction::Set,
-        help = "Enforce override of raw `borg check` options for all repositories.",
-        help_heading = "Override repository options",
-        id = args::CHECK_OPTIONS,
-        long = "check-options",
-        long_help = long_help::CHECK_OPTIONS,
+        env = args::REDACT,
+        hide_env = true,
+        help = "Replace matches of comma separated regular expressions in all outputs with ***.",
+        long_help = long_help::REDACT,
+        id = args::REDACT,
+        long = "redact",
         value_hint = ValueHint::Other,
-        value_name = "OPTS",
+        value_name = "PATTERNS",
         value_parser = value_parser!(String),
     )]
-    pub(crate) check_opts: Option<String>,
+    pub(crate) redact: Option<String>,
 
     #[arg(
         action = clap::ArgAction::Set,
-        help = "Local path to a specific 'borg' binary",
-        help_heading = "Override repository options",
-        id = args::BORG_BINARY,
-        long = "borg-binary",
-        long_help = long_help::BORG_BINARY,
-        value_hint = ValueHint::FilePath,
-        value_name = "FILE",
-        value_parser = value_parser!(std::path::PathBuf),
-        )]
-    pub(crate) borg_binary: Option<std::path::PathBuf>,
+        env = args::ROLLUP,
+        hide_env = true,
+        help = "Add a summary aggregated by <host>",
+        long_help = long_help::ROLLUP,
+        id = args::ROLLUP,
+        long = "rollup",
+        value_hint = ValueHint::Other,
+        value_name = "host",
+        value_parser = value_parser!(Rollup),
+    )]
+    pub(crate) rollup: Option<Rollup>,
 
     #[arg(
         action = clap::ArgAction::Set,
-        help = "Threshold to warn when the last archive is older than <HOURS>",
-        help_heading = "Override repository options",
-        id = args::MAX_AGE_HOURS,
-        long = "max-age-hours",
-        long_help = long_help::MAX_AGE_HOURS,
+        env = args::ERROR_LINES,
+        help = "Fold messages in the text report after <N> lines.",
+        hide_env = true,
+        id = args::ERROR_LINES,
+        long = "error-lines",
+        long_help = long_help::ERROR_LINES,
         value_hint = ValueHint::Other,
-        value_name = "HOURS",
-        value_parser = value_parser!(f64),
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
     )]
-    pub(crate) max_age_hours: Option<f64>,
+    pub(crate) error_lines: Option<usize>,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        env = args::NO_TRUNCATE,
+        hide_env = true,
+        help = "Show all lines of the messages in the text report.",
+        long_help = long_help::NO_TRUNCATE,
+        id = args::NO_TRUNCATE,
+        long = "no-truncate",
+    )]
+    pub(crate) no_truncate: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        help = "Prompt once for a passphrase used by all repositories without one.",
+        long_help = "Prompt once for a passphrase and provide it as BORG_PASSPHRASE to all repositories without BORG_PASSPHRASE, BORG_PASSCOMMAND or BORG_PASSPHRASE_FD. This requires an interactive terminal.",
+        id = args::ASK_PASSPHRASE,
+        long = "ask-passphrase",
+    )]
+    pub(crate) ask_passphrase: bool,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        conflicts_with_all = [args::ASK_PASSPHRASE, args::WATCH],
+        help = "Read the env file of a single <REPOSITORY> from stdin.",
+        long_help = "Read the env file of a single <REPOSITORY> from stdin. This avoids to store secrets on disk and can be combined with --env-dir. It cannot be combined with --watch, as stdin is read only once.",
+        id = args::ENV_STDIN,
+        long = "env-stdin",
+        value_hint = ValueHint::Other,
+        value_name = "REPOSITORY",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) env_stdin: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        help = "Validate the configuration of all repositories without running `borg`.",
+        long_help = "Validate the configuration of all repositories without running `borg`. Each invalid *.env file is reported and the exit code is non-zero, when any configuration is invalid.",
+        id = args::CONFIG_CHECK,
+        long = "config-check",
+    )]
+    pub(crate) config_check: bool,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        conflicts_with = args::CONFIG_CHECK,
+        help = "Print the resolved options and the `borg` environment of <REPOSITORY> without running `borg`.",
+        long_help = "Print the resolved options as BORGREPORT_* variables and the environment passed to `borg` for <REPOSITORY> without running `borg`. The values of secrets like BORG_PASSPHRASE and BORG_PASSCOMMAND are masked and the --redact patterns apply.",
+        id = args::PRINT_ENV,
+        long = "print-env",
+        value_hint = ValueHint::Other,
+        value_name = "REPOSITORY",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) print_env: Option<String>,
 
     #[arg(
         action = clap::ArgAction::SetTrue,
         exclusive = true,
-        hide = true,
-        id = args::HELP2MAN,
-        long = "help-man",
+        help = "Print the JSON Schema of the --json-to report and exit.",
+        long_help = "Print the JSON Schema of the report written by --json-to and exit. It describes the document of the current `schema_version` to validate the report or generate types from it.",
+        id = args::PRINT_JSON_SCHEMA,
+        long = "print-json-schema",
     )]
-    /// Print an extended help message as input for `help2man`
-    pub(crate) print_help2man: bool,
+    pub(crate) print_json_schema: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        conflicts_with_all = [args::CONFIG_CHECK, args::PRINT_ENV],
+        help = "Send a sample report to --mail-to without running `borg`.",
+        long_help = "Send a sample report to the --mail-to address without running `borg` to verify the mail delivery via `sendmail`. The exit code is non-zero, when the mail cannot be sent.",
+        id = args::TEST_MAIL,
+        long = "test-mail",
+        requires = args::MAILTOADDR,
+    )]
+    pub(crate) test_mail: bool,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        conflicts_with = args::CHECK,
+        help = "Query only `borg info` for a fast metrics collection.",
+        long_help = "Query only `borg info` for a fast metrics collection. This implies `--no-progress` and skips `borg check`, the oldest archive and the state directory. The sanity checks are still performed.",
+        id = args::FAST,
+        long = "fast",
+    )]
+    pub(crate) fast: bool,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        conflicts_with_all = [args::CONFIG_CHECK, args::WATCH],
+        help = "Summarize the state history of the <PERIOD> in a digest without running `borg`.",
+        long_help = "Summarize the state history of all repositories for the <PERIOD> in a digest without running `borg`: The number of runs, runs with errors or warnings, observed backups and the size trend per repository. The digest is written like the text and HTML report and requires a state directory.",
+        id = args::DIGEST,
+        long = "digest",
+        requires = args::STATE_DIR,
+        value_name = "PERIOD",
+        value_parser = value_parser!(Digest),
+    )]
+    pub(crate) digest: Option<Digest>,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        help = "Print a single summary line e.g. for a status bar.",
+        long_help = "Print a single summary line with the number of repositories per state and the total repository size e.g. `borg: 12 ok, 1 warn, 0 err (1.2TB)` for a status bar. It replaces the text report on stdout.",
+        id = args::ONELINE,
+        long = "oneline",
+    )]
+    pub(crate) oneline: bool,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        conflicts_with = args::ASK_PASSPHRASE,
+        help = "Run the report repeatedly every <INTERVAL> e.g. 30m, 1h or 1d.",
+        long_help = "Run the report repeatedly every <INTERVAL> e.g. 30m, 1h or 1d until SIGTERM or SIGINT is received. The repositories are collected again and all outputs are regenerated on each run.",
+        id = args::WATCH,
+        long = "watch",
+        value_hint = ValueHint::Other,
+        value_name = "INTERVAL",
+        value_parser = parse_interval,
+    )]
+    pub(crate) watch: Option<std::time::Duration>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Serve the latest report via HTTP on <ADDR> e.g. 127.0.0.1:9099.",
+        long_help = "Serve the latest report via HTTP on <ADDR> e.g. 127.0.0.1:9099. The metrics are available at `/metrics` and the HTML report at `/`. The report is regenerated on each run of `--watch`.",
+        id = args::SERVE,
+        long = "serve",
+        requires = args::WATCH,
+        value_hint = ValueHint::Other,
+        value_name = "ADDR",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) serve: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce a glob archives filter for all repositories.",
+        help_heading = "Override repository options",
+        id = args::GLOB_ARCHIVES,
+        long = "glob-archives",
+        long_help = long_help::GLOB_ARCHIVES,
+        value_hint = ValueHint::Other,
+        value_name = "GLOB",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) glob_archives: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce friendly names for the archive globs in the metrics.",
+        help_heading = "Override repository options",
+        id = args::GLOB_LABELS,
+        long = "glob-labels",
+        long_help = long_help::GLOB_LABELS,
+        value_hint = ValueHint::Other,
+        value_name = "GLOB=LABEL",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) glob_labels: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce the expected hostnames of the archive globs.",
+        help_heading = "Override repository options",
+        id = args::GLOB_HOSTNAMES,
+        long = "glob-hostnames",
+        long_help = long_help::GLOB_HOSTNAMES,
+        value_hint = ValueHint::Other,
+        value_name = "GLOB=HOSTNAME",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) glob_hostnames: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce named tiers of archive globs e.g. \"hourly=db-h-* daily=db-d-*\".",
+        help_heading = "Override repository options",
+        id = args::TIERS,
+        long = "tiers",
+        long_help = long_help::TIERS,
+        value_hint = ValueHint::Other,
+        value_name = "TIER=GLOB",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) tiers: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce the archive glob shown in the summary.",
+        help_heading = "Override repository options",
+        id = args::PRIMARY_GLOB,
+        long = "primary-glob",
+        long_help = long_help::PRIMARY_GLOB,
+        value_hint = ValueHint::Other,
+        value_name = "GLOB",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) primary_glob: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce virtual repositories by archive globs e.g. \"web=web-* db=db-*\".",
+        help_heading = "Override repository options",
+        id = args::VIRTUAL_REPOS,
+        long = "virtual-repos",
+        long_help = long_help::VIRTUAL_REPOS,
+        value_hint = ValueHint::Other,
+        value_name = "NAME=GLOB",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) virtual_repos: Option<String>,
+
+    // Note: `ArgAction::SetTrue` will cause `Arg::default_value` = `false` but we need `None` when the flag is not present. -> use default_missing_value
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to run (or not run) `borg check`",
+        help_heading = "Override repository options",
+        id = args::CHECK,
+        long = "check",
+        long_help = long_help::CHECK,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) check: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce the days to run `borg check` e.g. \"Sun 1\".",
+        help_heading = "Override repository options",
+        id = args::CHECK_SCHEDULE,
+        long = "check-schedule",
+        long_help = long_help::CHECK_SCHEDULE,
+        value_hint = ValueHint::Other,
+        value_name = "DAYS",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) check_schedule: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce override of raw `borg check` options for all repositories.",
+        help_heading = "Override repository options",
+        id = args::CHECK_OPTIONS,
+        long = "check-options",
+        long_help = long_help::CHECK_OPTIONS,
+        value_hint = ValueHint::Other,
+        value_name = "OPTS",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) check_opts: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce the policy for --repair in the `borg check` options for all repositories.",
+        help_heading = "Override repository options",
+        id = args::CHECK_REPAIR,
+        long = "check-repair",
+        long_help = long_help::CHECK_REPAIR,
+        value_hint = ValueHint::Other,
+        value_name = "deny|warn|allow",
+        value_parser = value_parser!(CheckRepair),
+    )]
+    pub(crate) check_repair: Option<CheckRepair>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to run (or not run) `borg prune`",
+        help_heading = "Override repository options",
+        id = args::PRUNE,
+        long = "prune",
+        long_help = long_help::PRUNE,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) prune: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce override of raw `borg prune` options for all repositories.",
+        help_heading = "Override repository options",
+        id = args::PRUNE_OPTIONS,
+        long = "prune-options",
+        long_help = long_help::PRUNE_OPTIONS,
+        value_hint = ValueHint::Other,
+        value_name = "OPTS",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) prune_opts: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce override of raw `borg info` options for all repositories.",
+        help_heading = "Override repository options",
+        id = args::INFO_OPTIONS,
+        long = "info-options",
+        long_help = long_help::INFO_OPTIONS,
+        value_hint = ValueHint::Other,
+        value_name = "OPTS",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) info_opts: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce to check <N> random archives instead of the last archive",
+        help_heading = "Override repository options",
+        id = args::CHECK_SAMPLE,
+        long = "check-sample",
+        long_help = long_help::CHECK_SAMPLE,
+        value_hint = ValueHint::Other,
+        value_name = "N",
+        value_parser = value_parser!(usize),
+    )]
+    pub(crate) check_sample: Option<usize>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce to ignore `borg check` output lines matching any <PATTERN>",
+        help_heading = "Override repository options",
+        id = args::CHECK_IGNORE_PATTERNS,
+        long = "check-ignore-patterns",
+        long_help = long_help::CHECK_IGNORE_PATTERNS,
+        value_hint = ValueHint::Other,
+        value_name = "PATTERN",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) check_ignore_patterns: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce to terminate `borg check` after <INTERVAL> e.g. 30m, 6h or 1d",
+        help_heading = "Override repository options",
+        id = args::CHECK_TIMEOUT,
+        long = "check-timeout",
+        long_help = long_help::CHECK_TIMEOUT,
+        value_hint = ValueHint::Other,
+        value_name = "INTERVAL",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) check_timeout: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::CHECK_OPTIONS_APPEND,
+        help = "Append raw `borg check` options to the options of all repositories.",
+        help_heading = "Override repository options",
+        hide_env = true,
+        id = args::CHECK_OPTIONS_APPEND,
+        long = "check-options-append",
+        long_help = long_help::CHECK_OPTIONS_APPEND,
+        value_hint = ValueHint::Other,
+        value_name = "OPTS",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) check_opts_append: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        env = args::KEEP_ENV,
+        help = "Inherit the space separated BORG_* <VARS> from the environment for all repositories.",
+        hide_env = true,
+        id = args::KEEP_ENV,
+        long = "keep-env",
+        long_help = long_help::KEEP_ENV,
+        value_hint = ValueHint::Other,
+        value_name = "VARS",
+        value_parser = value_parser!(String),
+    )]
+    pub(crate) keep_env: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Local path to a specific 'borg' binary",
+        help_heading = "Override repository options",
+        id = args::BORG_BINARY,
+        long = "borg-binary",
+        long_help = long_help::BORG_BINARY,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        value_parser = value_parser!(std::path::PathBuf),
+        )]
+    pub(crate) borg_binary: Option<std::path::PathBuf>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Version requirement for the 'borg' binary e.g. \">=1.2, <2\"",
+        help_heading = "Override repository options",
+        id = args::BORG_VERSION_REQ,
+        long = "borg-version-req",
+        long_help = long_help::BORG_VERSION_REQ,
+        value_hint = ValueHint::Other,
+        value_name = "REQ",
+        value_parser = NonEmptyStringValueParser::new(),
+    )]
+    pub(crate) borg_version_req: Option<String>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Threshold to warn when the last archive is older than <HOURS>",
+        help_heading = "Override repository options",
+        id = args::MAX_AGE_HOURS,
+        long = "max-age-hours",
+        long_help = long_help::MAX_AGE_HOURS,
+        value_hint = ValueHint::Other,
+        value_name = "HOURS",
+        value_parser = value_parser!(f64),
+    )]
+    pub(crate) max_age_hours: Option<f64>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Threshold to warn when the last successful check is older than <HOURS>",
+        help_heading = "Override repository options",
+        id = args::CHECK_MAX_INTERVAL_HOURS,
+        long = "check-max-interval-hours",
+        long_help = long_help::CHECK_MAX_INTERVAL_HOURS,
+        value_hint = ValueHint::Other,
+        value_name = "HOURS",
+        value_parser = value_parser!(f64),
+    )]
+    pub(crate) check_max_interval_hours: Option<f64>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to allow (or deny) empty repositories without a warning",
+        help_heading = "Override repository options",
+        id = args::ALLOW_EMPTY,
+        long = "allow-empty",
+        long_help = long_help::ALLOW_EMPTY,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) allow_empty: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to check (or not check) the archive start times for a stuck clock",
+        help_heading = "Override repository options",
+        id = args::CLOCK_CHECK,
+        long = "clock-check",
+        long_help = long_help::CLOCK_CHECK,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) clock_check: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to check (or not check) the repository manifest for a rollback",
+        help_heading = "Override repository options",
+        id = args::ROLLBACK_CHECK,
+        long = "rollback-check",
+        long_help = long_help::ROLLBACK_CHECK,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) rollback_check: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to mark (or unmark) all repositories as critical",
+        help_heading = "Override repository options",
+        id = args::CRITICAL,
+        long = "critical",
+        long_help = long_help::CRITICAL,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) critical: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce the maximum severity of all repositories in the aggregated status",
+        help_heading = "Override repository options",
+        id = args::SEVERITY,
+        long = "severity",
+        long_help = long_help::SEVERITY,
+        value_hint = ValueHint::Other,
+        value_name = "ignore|warning|error",
+        value_parser = value_parser!(Severity),
+    )]
+    pub(crate) severity: Option<Severity>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to report connection failures and timeouts as warnings (or errors)",
+        help_heading = "Override repository options",
+        id = args::REPO_TIMEOUT_AS_WARNING,
+        long = "repo-timeout-as-warning",
+        long_help = long_help::REPO_TIMEOUT_AS_WARNING,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) repo_timeout_as_warning: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce to show the <N> newest archives per archive glob",
+        help_heading = "Override repository options",
+        id = args::LAST_ARCHIVES,
+        long = "last-archives",
+        long_help = long_help::LAST_ARCHIVES,
+        value_hint = ValueHint::Other,
+        value_name = "N",
+        value_parser = value_parser!(usize),
+    )]
+    pub(crate) last_archives: Option<usize>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce to select the newest archives by <time> or <name>",
+        help_heading = "Override repository options",
+        id = args::LAST_BY,
+        long = "last-by",
+        long_help = long_help::LAST_BY,
+        value_hint = ValueHint::Other,
+        value_name = "time|name",
+        value_parser = value_parser!(LastBy),
+    )]
+    pub(crate) last_by: Option<LastBy>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to run `borg` with (or without) `--log-json`",
+        help_heading = "Override repository options",
+        id = args::LOG_JSON,
+        long = "log-json",
+        long_help = long_help::LOG_JSON,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) log_json: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to run `borg info` with (or without) `--bypass-lock`",
+        help_heading = "Override repository options",
+        id = args::BYPASS_LOCK,
+        long = "bypass-lock",
+        long_help = long_help::BYPASS_LOCK,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) bypass_lock: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to allow (or deny) the access to unknown unencrypted repositories",
+        help_heading = "Override repository options",
+        alias = "unknown-unencrypted-repo-ok",
+        id = args::UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK,
+        long = "unknown-unencrypted-repo-access-is-ok",
+        long_help = long_help::UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) unknown_unencrypted_repo_access_is_ok: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        default_missing_value = "true",
+        help = "Enforce to allow (or deny) the access to relocated repositories",
+        help_heading = "Override repository options",
+        alias = "relocated-repo-ok",
+        id = args::RELOCATED_REPO_ACCESS_IS_OK,
+        long = "relocated-repo-access-is-ok",
+        long_help = long_help::RELOCATED_REPO_ACCESS_IS_OK,
+        num_args = 0..=1,
+        require_equals = true,
+        hide_possible_values = true,
+        value_hint = ValueHint::Other,
+        value_name = "true|false",
+        value_parser = value_parser!(bool),
+    )]
+    pub(crate) relocated_repo_access_is_ok: Option<bool>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Threshold to warn when the repository grew by more than <PERCENT> since the previous run",
+        help_heading = "Override repository options",
+        id = args::MAX_GROWTH_PERCENT,
+        long = "max-growth-percent",
+        long_help = long_help::MAX_GROWTH_PERCENT,
+        value_hint = ValueHint::Other,
+        value_name = "PERCENT",
+        value_parser = value_parser!(f64),
+    )]
+    pub(crate) max_growth_percent: Option<f64>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Threshold to warn when the repository size deviates by more than <N> standard deviations from its moving average",
+        help_heading = "Override repository options",
+        id = args::ANOMALY_SIGMA,
+        long = "anomaly-sigma",
+        long_help = long_help::ANOMALY_SIGMA,
+        value_hint = ValueHint::Other,
+        value_name = "N",
+        value_parser = value_parser!(f64),
+    )]
+    pub(crate) anomaly_sigma: Option<f64>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Threshold to warn when the filesystem of a local repository has less than <BYTES> available",
+        help_heading = "Override repository options",
+        id = args::MIN_FREE_BYTES,
+        long = "min-free-bytes",
+        long_help = long_help::MIN_FREE_BYTES,
+        value_hint = ValueHint::Other,
+        value_name = "BYTES",
+        value_parser = value_parser!(u64),
+    )]
+    pub(crate) min_free_bytes: Option<u64>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Threshold to warn when the filesystem of a local repository has less than <PERCENT> available",
+        help_heading = "Override repository options",
+        id = args::MIN_FREE_PERCENT,
+        long = "min-free-percent",
+        long_help = long_help::MIN_FREE_PERCENT,
+        value_hint = ValueHint::Other,
+        value_name = "PERCENT",
+        value_parser = value_parser!(f64),
+    )]
+    pub(crate) min_free_percent: Option<f64>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Threshold to warn when the repository uses more than <PERCENT> of its storage quota",
+        help_heading = "Override repository options",
+        id = args::QUOTA_WARN_PERCENT,
+        long = "quota-warn-percent",
+        long_help = long_help::QUOTA_WARN_PERCENT,
+        value_hint = ValueHint::Other,
+        value_name = "PERCENT",
+        value_parser = value_parser!(f64),
+    )]
+    pub(crate) quota_warn_percent: Option<f64>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Threshold to warn when the repository was not modified in the last <N> runs",
+        help_heading = "Override repository options",
+        id = args::STALE_RUNS,
+        long = "stale-runs",
+        long_help = long_help::STALE_RUNS,
+        value_hint = ValueHint::Other,
+        value_name = "N",
+        value_parser = value_parser!(usize),
+    )]
+    pub(crate) stale_runs: Option<usize>,
+
+    #[arg(
+        action = clap::ArgAction::Set,
+        help = "Enforce when to send the report per mail for all repositories",
+        help_heading = "Override repository options",
+        id = args::MAIL_ON,
+        long = "mail-on",
+        long_help = long_help::MAIL_ON,
+        value_hint = ValueHint::Other,
+        value_name = "always|warning|error",
+        value_parser = value_parser!(MailOn),
+    )]
+    pub(crate) mail_on: Option<MailOn>,
+
+    #[arg(
+        action = clap::ArgAction::SetTrue,
+        exclusive = true,
+        hide = true,
+        id = args::HELP2MAN,
+        long = "help-man",
+    )]
+    /// Print an extended help message as input for `help2man`
+    pub(crate) print_help2man: bool,
+}
+
+mod tests {
+    #[test]
+    fn parse_interval() {
+        use super::parse_interval;
+        use std::time::Duration;
+
+        assert_eq!(parse_interval("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_interval("1d"), Ok(Duration::from_secs(24 * 60 * 60)));
+        assert!(parse_interval("0h").is_err());
+        assert!(parse_interval("1w").is_err());
+        assert!(parse_interval(&format!("{}d", u64::MAX)).is_err());
+    }
 }
@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::{cli, report::Component, state::State};
+
+/// A digest summarizes the state history of all repositories over a period
+pub(crate) struct Digest {
+    /// The summarized period
+    pub(crate) period: cli::Digest,
+    /// Start of the period
+    pub(crate) since: jiff::Timestamp,
+    /// One entry per repository
+    pub(crate) entries: Vec<DigestEntry>,
+}
+impl Component for Digest {}
+
+/// The runs of a single repository in the period of a `Digest`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct DigestEntry {
+    /// Name of the repository
+    pub(crate) repository: String,
+    /// Number of runs
+    pub(crate) runs: usize,
+    /// Number of runs with errors
+    pub(crate) failed: usize,
+    /// Number of runs with warnings but without errors
+    pub(crate) warned: usize,
+    /// Number of runs, which saw a modified repository (e.g. a new backup)
+    pub(crate) backups: usize,
+    /// Total deduplicated compressed repository size at the end of the period
+    pub(crate) unique_csize: i64,
+    /// Change of the repository size in the period
+    pub(crate) growth: i64,
+}
+
+impl DigestEntry {
+    /// Describe the runs with errors or warnings e.g. "2 runs with errors, 1 run with warnings"
+    pub(crate) fn attention(&self) -> Option<String> {
+        let runs = |n: usize| {
+            if n == 1 {
+                "1 run".to_string()
+            } else {
+                format!("{n} runs")
+            }
+        };
+        let mut messages = Vec::new();
+        if self.failed > 0 {
+            messages.push(format!("{} with errors", runs(self.failed)));
+        }
+        if self.warned > 0 {
+            messages.push(format!("{} with warnings", runs(self.warned)));
+        }
+        (!messages.is_empty()).then(|| messages.join(", "))
+    }
+}
+
+impl Digest {
+    /// Create an empty digest of the `period` ending now
+    pub(crate) fn new(period: cli::Digest) -> anyhow::Result<Self> {
+        let since =
+            jiff::Timestamp::now().checked_sub(jiff::SignedDuration::try_from(period.period())?)?;
+        Ok(Self {
+            period,
+            since,
+            entries: Vec::new(),
+        })
+    }
+
+    /// Add the runs of the `repository` in the period from its `state`
+    pub(crate) fn add(&mut self, repository: &str, state: &State) {
+        let start = state
+            .history
+            .iter()
+            .position(|run| run.timestamp >= self.since)
+            .unwrap_or(state.history.len());
+        let runs = &state.history[start..];
+        // The last run before the period is the baseline for modifications and the size
+        let baseline = start.checked_sub(1).map(|i| &state.history[i]);
+        let mut entry = DigestEntry {
+            repository: repository.to_string(),
+            runs: runs.len(),
+            failed: runs.iter().filter(|run| run.errors > 0).count(),
+            warned: runs
+                .iter()
+                .filter(|run| run.errors == 0 && run.warnings > 0)
+                .count(),
+            ..Default::default()
+        };
+        let mut previous = baseline;
+        for run in runs {
+            if run.last_modified.is_some()
+                && previous.is_some_and(|p| p.last_modified != run.last_modified)
+            {
+                entry.backups += 1;
+            }
+            previous = Some(run);
+        }
+        if let Some(last) = runs.last() {
+            entry.unique_csize = last.unique_csize;
+            entry.growth =
+                last.unique_csize - baseline.or(runs.first()).map_or(0, |r| r.unique_csize);
+        }
+        self.entries.push(entry);
+    }
+}
+
+mod tests {
+    #[test]
+    fn add() {
+        use crate::{
+            cli,
+            digest::Digest,
+            state::{Run, State},
+        };
+        use jiff::{civil::date, SignedDuration, Timestamp};
+
+        let modified = |day: i8| Some(date(2024, 1, day).at(0, 0, 0, 0));
+        let run = |timestamp: Timestamp, unique_csize, last_modified, errors, warnings| Run {
+            timestamp,
+            unique_csize,
+            last_modified,
+            errors,
+            warnings,
+        };
+        #[allow(clippy::unwrap_used)]
+        let mut digest = Digest::new(cli::Digest::Weekly).unwrap();
+        let before = digest.since - SignedDuration::from_hours(1);
+        let within = digest.since + SignedDuration::from_hours(1);
+        let state = State {
+            history: vec![
+                run(before, 100, modified(1), 1, 0),
+                run(within, 100, modified(1), 0, 1),
+                run(within, 150, modified(2), 1, 1),
+                run(within, 150, modified(2), 0, 0),
+                run(within, 180, modified(3), 0, 0),
+            ],
+            ..Default::default()
+        };
+        digest.add("repo", &state);
+        let entry = &digest.entries[0];
+        assert_eq!(
+            (entry.runs, entry.failed, entry.warned, entry.backups),
+            (4, 1, 1, 2)
+        );
+        assert_eq!((entry.unique_csize, entry.growth), (180, 80));
+        assert_eq!(
+            entry.attention().as_deref(),
+            Some("1 run with errors, 1 run with warnings")
+        );
+    }
+}
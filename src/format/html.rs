@@ -1,9 +1,19 @@
 // SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use super::{Formattable, Formatter};
-use crate::report::{BulletPoint, ChecksEntry, Report, Section, SummaryEntry};
-use human_repr::{HumanCount, HumanDuration};
+use super::{
+    format_percent, human_delta_bytes, human_duration, truncate, Formattable, Formatter,
+    COMMAND_WIDTH,
+};
+use crate::{
+    cli::{self, Rollup},
+    digest::Digest,
+    report::{
+        BulletPoint, CheckChange, ChecksEntry, HostRollup, PruneEntry, Report, Section,
+        SummaryEntry,
+    },
+};
+use human_repr::HumanCount;
 
 /// Html `Formatter` (text/html)
 pub struct Html;
@@ -15,7 +25,8 @@ impl Formatter<Report> for Html {
         let now = jiff::Zoned::now();
 
         let title = format!(
-            "Backup report ({})",
+            "{}Backup report ({})",
+            super::environment_prefix(),
             jiff::fmt::strtime::format("%F", &now).unwrap_or_default()
         );
 
@@ -52,20 +63,45 @@ impl Formatter<Report> for Html {
                 border: 1px solid black;
                 font-family: monospace, sans-serif;
             }}
+            .badge {{
+                border-radius: 3px;
+                color: white;
+                font-family: sans-serif;
+                font-size: smaller;
+                padding: 1px 4px;
+            }}
+            .ok {{
+                background-color: green;
+            }}
+            .warning {{
+                background-color: darkorange;
+            }}
+            .error {{
+                background-color: red;
+            }}
+            tr.error td {{
+                background-color: mistyrose;
+            }}
+            tr.warning td {{
+                background-color: papayawhip;
+            }}
         </style>
     </head>
     <body>
-        <h1>{title}</h1>"#,
+        {}<h1>{title}</h1>"#,
             env!("CARGO_PKG_NAME"),
             env!("CARGO_PKG_VERSION"),
             env!("CARGO_PKG_LICENSE"),
+            Self::environment_banner(),
         )?;
 
+        Self::format_toc(buf, data)?;
+
         if data.has_errors() {
             write!(
                 buf,
-                r"
-        <h2>Errors</h2>"
+                r#"
+        <h2 id="errors">Errors</h2>"#
             )?;
             data.errors.format(buf, Self)?;
         }
@@ -73,35 +109,55 @@ impl Formatter<Report> for Html {
         if data.has_warnings() {
             write!(
                 buf,
-                r"
-        <h2>Warnings</h2>"
+                r#"
+        <h2 id="warnings">Warnings</h2>"#
             )?;
             data.warnings.format(buf, Self)?;
         }
 
-        if !data.summary.is_empty() {
-            write!(
-                buf,
-                r"
-        <h2>Summary</h2>"
-            )?;
-            data.summary.format(buf, Self)?;
-        }
+        Self::format_ok(buf, data)?;
 
-        if !data.checks.is_empty() {
+        Self::format_summary(buf, data)?;
+
+        Self::format_rollup(buf, data)?;
+
+        Self::format_checks(buf, data)?;
+
+        if !data.notes.is_empty() {
             write!(
                 buf,
-                r"
-        <h2><code>borg check</code> result</h2>"
+                r#"
+        <h2 id="notes">Notes</h2>"#
             )?;
-            data.checks.format(buf, Self)?;
+            data.notes.format(buf, Self)?;
         }
 
-        // Footer
+        Self::format_footer(buf, data, &now)
+    }
+}
+
+impl Html {
+    /// Add the footer with the savings of all repositories and close the document
+    fn format_footer<W>(buf: &mut W, data: &Report, now: &jiff::Zoned) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        let savings = data
+            .savings()
+            .filter(|_| cli::args().show_savings)
+            .map(|(total_size, unique_csize, ratio)| {
+                format!(
+                    "\n            <p>Savings: {} of {} source stored as {}</p>",
+                    format_percent(ratio),
+                    total_size.human_count_bytes(),
+                    unique_csize.human_count_bytes()
+                )
+            })
+            .unwrap_or_default();
         write!(
             buf,
             r#"
-        <footer>
+        <footer>{savings}
             <p>
                 Generated on {} with <a href="{}" target="_blank">{}</a> {}
             </p>
@@ -109,12 +165,228 @@ impl Formatter<Report> for Html {
     </body>
 </html>
 "#,
-            jiff::fmt::rfc2822::to_string(&now).unwrap_or_default(),
+            jiff::fmt::rfc2822::to_string(now).unwrap_or_default(),
             env!("CARGO_PKG_REPOSITORY"),
             env!("CARGO_PKG_NAME"),
             env!("CARGO_PKG_VERSION"),
         )
     }
+
+    /// Link the sections of the report in a table of contents, when there is more than one section
+    fn format_toc<W>(buf: &mut W, data: &Report) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        let sections = [
+            (data.has_errors(), "errors", "Errors"),
+            (data.has_warnings(), "warnings", "Warnings"),
+            (
+                cli::args().show_ok && !data.healthy_repositories().is_empty(),
+                "ok",
+                "OK",
+            ),
+            (!data.summary.is_empty(), "summary", "Summary"),
+            (
+                cli::args().rollup == Some(Rollup::Host) && !data.summary.is_empty(),
+                "summary-by-host",
+                "Summary by host",
+            ),
+            (!data.checks.is_empty(), "checks", "Check result"),
+            (!data.prunes.is_empty(), "prunes", "Prune result"),
+            (!data.notes.is_empty(), "notes", "Notes"),
+        ];
+        let links: Vec<String> = sections
+            .iter()
+            .filter(|(present, _, _)| *present)
+            .map(|(_, id, title)| format!(r##"<a href="#{id}">{title}</a>"##))
+            .collect();
+        if links.len() > 1 {
+            write!(
+                buf,
+                r"
+        <nav>{}</nav>",
+                links.join(" | ")
+            )?;
+        }
+        Ok(())
+    }
+
+    /// List all healthy repositories in a compact OK section
+    fn format_ok<W>(buf: &mut W, data: &Report) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        let healthy = data.healthy_repositories();
+        if cli::args().show_ok && !healthy.is_empty() {
+            write!(
+                buf,
+                r#"
+        <h2 id="ok">OK</h2>
+        <p>{}</p>"#,
+                healthy.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Add the summary aggregated by the `--rollup` option
+    fn format_rollup<W>(buf: &mut W, data: &Report) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        if cli::args().rollup == Some(Rollup::Host) && !data.summary.is_empty() {
+            write!(
+                buf,
+                r#"
+        <h2 id="summary-by-host">Summary by host</h2>"#
+            )?;
+            data.summary.rollup_by_host().format(buf, Self)?;
+        }
+        Ok(())
+    }
+
+    /// Add the result of `borg check` and `borg prune`
+    fn format_checks<W>(buf: &mut W, data: &Report) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        if !data.checks.is_empty() {
+            write!(
+                buf,
+                r#"
+        <h2 id="checks"><code>borg check</code> result</h2>"#
+            )?;
+            data.checks.format(buf, Self)?;
+        }
+        if !data.prunes.is_empty() {
+            write!(
+                buf,
+                r#"
+        <h2 id="prunes"><code>borg prune</code> result</h2>"#
+            )?;
+            data.prunes.format(buf, Self)?;
+        }
+        Ok(())
+    }
+
+    /// Add the optional cells of a summary row and close the row
+    fn format_summary_optional_cells<W>(
+        buf: &mut W,
+        e: &SummaryEntry,
+        show_growth: bool,
+        show_delta: bool,
+    ) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        if show_growth {
+            write!(
+                buf,
+                r#"
+                    <td style="text-align:right">{}</td>"#,
+                e.growth.map(human_delta_bytes).unwrap_or_default()
+            )?;
+        }
+        if show_delta {
+            write!(
+                buf,
+                r#"
+                    <td style="text-align:right">{}</td>"#,
+                e.original_size_delta
+                    .map(human_delta_bytes)
+                    .unwrap_or_default()
+            )?;
+        }
+        if cli::args().show_savings {
+            write!(
+                buf,
+                r#"
+                    <td style="text-align:right">{}</td>"#,
+                e.savings().map(format_percent).unwrap_or_default()
+            )?;
+        }
+        if cli::args().show_encryption {
+            write!(
+                buf,
+                r"
+                    <td>{}</td>",
+                e.encryption
+            )?;
+        }
+        if cli::args().show_command {
+            write!(
+                buf,
+                r#"
+                    <td title="{}">{}</td>"#,
+                escape(&e.command_line),
+                escape(&truncate(&e.command_line, COMMAND_WIDTH))
+            )?;
+        }
+        if cli::args().show_comment {
+            write!(
+                buf,
+                r#"
+                    <td title="{}">{}</td>"#,
+                escape(&e.comment),
+                escape(&truncate(&e.comment, COMMAND_WIDTH))
+            )?;
+        }
+        write!(
+            buf,
+            r"
+                </tr>"
+        )?;
+        Ok(())
+    }
+
+    /// Add the table header of the summary with the optional columns
+    fn format_summary_header<W>(
+        buf: &mut W,
+        show_tier: bool,
+        optional_columns: &[(bool, &str)],
+    ) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        write!(
+            buf,
+            r"
+        <table>
+            <thead>
+                <tr>
+                    <th>Repository</th>{}
+                    <th>Hostname</th>
+                    <th>Last archive</th>
+                    <th>Start</th>
+                    <th>Oldest</th>
+                    <th>Duration</th>
+                    <th>Source</th>
+                    <th>Δ Archive</th>
+                    <th>∑ Source</th>
+                    <th>∑ Repository</th>
+                    <th>Dedup</th>",
+            if show_tier {
+                "\n                    <th>Tier</th>"
+            } else {
+                ""
+            }
+        )?;
+        for (_, column) in optional_columns.iter().filter(|(show, _)| *show) {
+            write!(
+                buf,
+                r"
+                    <th>{column}</th>"
+            )?;
+        }
+        write!(
+            buf,
+            r"
+                </tr>
+            </thead>
+            <tbody>"
+        )?;
+        Ok(())
+    }
 }
 
 impl Formatter<Section<BulletPoint>> for Html {
@@ -122,37 +394,189 @@ impl Formatter<Section<BulletPoint>> for Html {
     where
         W: std::fmt::Write,
     {
-        // Print all lines of the section entry under one bullet point
+        // Print all lines of the section entry in one message cell
         write!(
             buf,
             r"
-        <ul>"
+        <table>
+            <thead>
+                <tr>
+                    <th>Repository</th>
+                    <th>Message</th>
+                </tr>
+            </thead>
+            <tbody>"
         )?;
         for entry in data.dedup_inner() {
-            let mut lines = entry.trim().lines();
-            if let Some(line) = lines.next() {
-                write!(
-                    buf,
-                    r"
-            <li>{line}"
-                )?;
-            }
-            for line in lines {
-                write!(buf, r"<br>{line}")?;
-            }
-            write!(buf, r"</li>")?;
+            write!(
+                buf,
+                r#"
+                <tr>
+                    <td>{}</td>
+                    <td style="white-space:normal">{}</td>
+                </tr>"#,
+                entry.origin(),
+                entry
+                    .message()
+                    .trim()
+                    .lines()
+                    .collect::<Vec<_>>()
+                    .join("<br>")
+            )?;
         }
         write!(
             buf,
             r"
-        </ul>"
+            <tbody>
+        </table>"
         )?;
         Ok(())
     }
 }
 
-impl Formatter<Section<SummaryEntry>> for Html {
-    fn format<W>(buf: &mut W, data: &Section<SummaryEntry>) -> std::fmt::Result
+impl Html {
+    /// The banner of the `--environment`, which is prominent for a production environment e.g. "prod"
+    fn environment_banner() -> String {
+        cli::args()
+            .environment
+            .as_ref()
+            .map(|environment| {
+                let color = if environment.to_lowercase().starts_with("prod") {
+                    "firebrick"
+                } else {
+                    "slategray"
+                };
+                format!(
+                    r#"<div style="background-color: {color}; color: white; font-size: larger; font-weight: bold; padding: 8px; text-transform: uppercase">{}</div>
+        "#,
+                    escape(environment)
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// The status badge of the `repository` in the `report`
+    fn badge(report: &Report, repository: &str) -> &'static str {
+        if report.has_errors_for(repository) {
+            "error"
+        } else if report.has_warnings_for(repository) {
+            "warning"
+        } else {
+            "ok"
+        }
+    }
+
+    /// Add the summary table with a status badge of the repository in each row
+    fn format_summary<W>(buf: &mut W, report: &Report) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        let data = &report.displayed_summary();
+        if data.is_empty() {
+            return Ok(());
+        }
+        write!(
+            buf,
+            r#"
+        <h2 id="summary">Summary</h2>"#
+        )?;
+        let show_growth = data.iter().any(|e| e.growth.is_some());
+        let show_delta = data.iter().any(|e| e.original_size_delta.is_some());
+        let show_tier = data.iter().any(|e| e.tier.is_some());
+        Self::format_summary_header(
+            buf,
+            show_tier,
+            &[
+                (show_growth, "Δ Repository"),
+                (show_delta, "Δ Source"),
+                (cli::args().show_savings, "Savings"),
+                (cli::args().show_encryption, "Encryption"),
+                (cli::args().show_command, "Command"),
+                (cli::args().show_comment, "Comment"),
+            ],
+        )?;
+
+        // Repositories with errors come first, followed by warnings and healthy ones in name order
+        let badge = |repository: &str| Self::badge(report, repository);
+        let rank = |badge| ["error", "warning", "ok"].iter().position(|b| *b == badge);
+        let mut rows: Vec<_> = data.inner().iter().collect();
+        rows.sort_by(|a, b| {
+            (rank(badge(&a.repository)), &a.repository)
+                .cmp(&(rank(badge(&b.repository)), &b.repository))
+        });
+
+        // The first row of each repository is an anchor e.g. `#repo-name`
+        let mut previous_repository = None;
+        for e in rows {
+            let anchor = if previous_repository == Some(&e.repository) {
+                String::new()
+            } else {
+                format!(r#" id="repo-{}""#, slug(&e.repository))
+            };
+            previous_repository = Some(&e.repository);
+            let badge = badge(&e.repository);
+            let class = if badge == "ok" {
+                String::new()
+            } else {
+                format!(r#" class="{badge}""#)
+            };
+            let tier = if show_tier {
+                format!(
+                    "\n                    <td>{}</td>",
+                    e.tier.as_deref().unwrap_or_default()
+                )
+            } else {
+                String::new()
+            };
+            write!(
+                buf,
+                r#"
+                <tr{}{class}>
+                    <td><span class="badge {badge}">{badge}</span> {}</td>{}
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>"#,
+                anchor,
+                e.repository,
+                tier,
+                e.hostname,
+                e.archive,
+                jiff::fmt::strtime::format("%F", e.start).unwrap_or_else(|_| String::default()),
+                e.oldest
+                    .and_then(|t| jiff::fmt::strtime::format("%F", t).ok())
+                    .unwrap_or_default(),
+                human_duration(e.duration),
+                e.original_size.human_count_bytes(),
+                e.deduplicated_size.human_count_bytes(),
+                e.total_size.human_count_bytes(),
+                e.unique_csize.human_count_bytes(),
+                e.dedup_ratio()
+                    .map(|r| format!("{r:.1}x"))
+                    .unwrap_or_default()
+            )?;
+            Self::format_summary_optional_cells(buf, e, show_growth, show_delta)?;
+        }
+
+        write!(
+            buf,
+            r"
+            <tbody>
+        </table>"
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Formatter<Section<PruneEntry>> for Html {
+    fn format<W>(buf: &mut W, data: &Section<PruneEntry>) -> std::fmt::Result
     where
         W: std::fmt::Write,
     {
@@ -163,10 +587,64 @@ impl Formatter<Section<SummaryEntry>> for Html {
             <thead>
                 <tr>
                     <th>Repository</th>
-                    <th>Hostname</th>
-                    <th>Last archive</th>
-                    <th>Start</th>
+                    <th>Glob</th>
                     <th>Duration</th>
+                    <th>Okay</th>
+                    <th>Pruned</th>
+                    <th>Kept</th>
+                    <th>Deleted</th>
+                </tr>
+            </thead>
+            <tbody>"
+        )?;
+        for e in data.inner() {
+            write!(
+                buf,
+                r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                </tr>"#,
+                e.repository,
+                e.archive_glob.as_deref().unwrap_or_default(),
+                human_duration(e.duration),
+                if e.status.success() { "yes" } else { "no" },
+                e.pruned,
+                e.kept,
+                e.deleted_size
+                    .map(|size| size.unsigned_abs().human_count_bytes().to_string())
+                    .unwrap_or_default()
+            )?;
+        }
+        write!(
+            buf,
+            r"
+            <tbody>
+        </table>"
+        )?;
+        Ok(())
+    }
+}
+
+impl Formatter<HostRollup> for Html {
+    fn format<W>(buf: &mut W, data: &HostRollup) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        write!(
+            buf,
+            r"
+        <table>
+            <thead>
+                <tr>
+                    <th>Hostname</th>
+                    <th>Repositories</th>
+                    <th>Archives</th>
                     <th>Source</th>
                     <th>Δ Archive</th>
                     <th>∑ Repository</th>
@@ -175,25 +653,21 @@ impl Formatter<Section<SummaryEntry>> for Html {
             <tbody>"
         )?;
 
-        for e in data.inner() {
+        for e in &data.0 {
             write!(
                 buf,
                 r#"
                 <tr>
-                    <td>{}</td>
-                    <td>{}</td>
-                    <td>{}</td>
                     <td>{}</td>
                     <td style="text-align:right">{}</td>
                     <td style="text-align:right">{}</td>
                     <td style="text-align:right">{}</td>
                     <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
                 </tr>"#,
-                e.repository,
                 e.hostname,
-                e.archive,
-                jiff::fmt::strtime::format("%F", e.start).unwrap_or_else(|_| String::default()),
-                e.duration.as_secs_f64().human_duration(),
+                e.repositories,
+                e.archives,
                 e.original_size.human_count_bytes(),
                 e.deduplicated_size.human_count_bytes(),
                 e.unique_csize.human_count_bytes()
@@ -216,6 +690,7 @@ impl Formatter<Section<ChecksEntry>> for Html {
     where
         W: std::fmt::Write,
     {
+        let show_exit_codes = cli::args().show_exit_codes;
         write!(
             buf,
             r"
@@ -225,7 +700,32 @@ impl Formatter<Section<ChecksEntry>> for Html {
                     <th>Repository</th>
                     <th>Archive</th>
                     <th>Duration</th>
-                    <th>Okay</th>
+                    <th>Okay</th>"
+        )?;
+        if show_exit_codes {
+            write!(
+                buf,
+                r"
+                    <th>Exit code</th>"
+            )?;
+        }
+        if cli::args().show_resources {
+            write!(
+                buf,
+                r"
+                    <th>Memory</th>"
+            )?;
+        }
+        if cli::args().show_check_changes {
+            write!(
+                buf,
+                r"
+                    <th>Change</th>"
+            )?;
+        }
+        write!(
+            buf,
+            r"
                 </tr>
             </thead>
             <tbody>"
@@ -239,13 +739,43 @@ impl Formatter<Section<ChecksEntry>> for Html {
                     <td>{}</td>
                     <td>{}</td>
                     <td style="text-align:right">{}</td>
-                    <td style="text-align:right">{}</td>
-                </tr>"#,
+                    <td style="text-align:right">{}</td>"#,
                 e.repository,
                 e.archive_name.clone().unwrap_or_default(),
-                e.duration.as_secs_f64().human_duration(),
+                human_duration(e.duration),
                 if e.status.success() { "yes" } else { "no" }
             )?;
+            if show_exit_codes {
+                write!(
+                    buf,
+                    r#"
+                    <td style="text-align:right">{}</td>"#,
+                    e.exit_code()
+                )?;
+            }
+            if cli::args().show_resources {
+                write!(
+                    buf,
+                    r#"
+                    <td style="text-align:right">{}</td>"#,
+                    e.peak_rss
+                        .map(|b| b.human_count_bytes().to_string())
+                        .unwrap_or_default()
+                )?;
+            }
+            if cli::args().show_check_changes {
+                write!(
+                    buf,
+                    r"
+                    <td><strong>{}</strong></td>",
+                    e.change.map(CheckChange::as_str).unwrap_or_default()
+                )?;
+            }
+            write!(
+                buf,
+                r"
+                </tr>"
+            )?;
         }
 
         write!(
@@ -258,3 +788,176 @@ impl Formatter<Section<ChecksEntry>> for Html {
         Ok(())
     }
 }
+
+impl Formatter<Digest> for Html {
+    fn format<W>(buf: &mut W, data: &Digest) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        write!(
+            buf,
+            r#"<!DOCTYPE html>
+<html lang="en">
+    <head>
+        <meta charset=utf-8>
+        <meta name=generator content="{} {}">
+        <meta name=viewport content="width=device-width, initial-scale=1, minimum-scale=1">
+        <title>Backup digest {period}</title>
+    </head>
+    <body>
+        <h1>Backup digest {period} (since {since})</h1>"#,
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            period = data.period.as_str(),
+            since = jiff::fmt::strtime::format("%F", data.since).unwrap_or_default(),
+        )?;
+
+        Self::format_attention(buf, data)?;
+
+        write!(
+            buf,
+            r"
+        <h2>Summary</h2>
+        <table>
+            <thead>
+                <tr>
+                    <th>Repository</th>
+                    <th>Runs</th>
+                    <th>Errors</th>
+                    <th>Warnings</th>
+                    <th>Backups</th>
+                    <th>∑ Repository</th>
+                    <th>Δ Period</th>
+                </tr>
+            </thead>
+            <tbody>"
+        )?;
+
+        for e in &data.entries {
+            write!(
+                buf,
+                r#"
+                <tr>
+                    <td>{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                    <td style="text-align:right">{}</td>
+                </tr>"#,
+                e.repository,
+                e.runs,
+                e.failed,
+                e.warned,
+                e.backups,
+                e.unique_csize.human_count_bytes(),
+                human_delta_bytes(e.growth)
+            )?;
+        }
+
+        write!(
+            buf,
+            r#"
+            <tbody>
+        </table>
+        <footer>
+            <p>
+                Generated on {} with <a href="{}" target="_blank">{}</a> {}
+            </p>
+        </footer>
+    </body>
+</html>
+"#,
+            jiff::fmt::rfc2822::to_string(&jiff::Zoned::now()).unwrap_or_default(),
+            env!("CARGO_PKG_REPOSITORY"),
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+        )
+    }
+}
+
+impl Html {
+    /// Format the repositories with errors or warnings in the period of the digest
+    fn format_attention<W>(buf: &mut W, data: &Digest) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        let attention: Vec<_> = data
+            .entries
+            .iter()
+            .filter_map(|e| e.attention().map(|a| (&e.repository, a)))
+            .collect();
+        if !attention.is_empty() {
+            write!(
+                buf,
+                r"
+        <h2>Attention</h2>
+        <ul>"
+            )?;
+            for (repository, message) in attention {
+                write!(
+                    buf,
+                    r"
+            <li>{repository}: {message}</li>"
+                )?;
+            }
+            write!(
+                buf,
+                r"
+        </ul>"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Escape the special characters `& < > "` of HTML in the `text` e.g. of an attribute value
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A safe HTML id from the `text`, which replaces all characters except `A-Za-z0-9-_` by `-` e.g. `nas/host1` to `nas-host1`
+fn slug(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+mod tests {
+    #[test]
+    fn slug() {
+        use super::slug;
+
+        assert_eq!(slug("nas/host1"), "nas-host1");
+        assert_eq!(slug(r#"a"><script>"#), "a---script-");
+        assert_eq!(slug("db_01-x"), "db_01-x");
+    }
+
+    #[test]
+    fn escape() {
+        use super::escape;
+
+        assert_eq!(
+            escape(r#"borg create --exclude "*.tmp" ::a /srv/<db>&co"#),
+            "borg create --exclude &quot;*.tmp&quot; ::a /srv/&lt;db&gt;&amp;co"
+        );
+        assert_eq!(escape(""), "");
+    }
+}
@@ -0,0 +1,249 @@
+// SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::Formatter;
+use crate::{
+    cli,
+    report::{BulletPoint, ChecksEntry, PruneEntry, Report, Section, SummaryEntry},
+};
+use schemars::JsonSchema;
+use serde::{Serialize, Serializer};
+
+/// Version of the JSON document. It is increased on breaking changes e.g. a renamed or removed field.
+const SCHEMA_VERSION: u32 = 1;
+
+/// The JSON document of a `Report`
+#[derive(Serialize, JsonSchema)]
+#[schemars(rename = "Report")]
+struct Document<'a> {
+    schema_version: u32,
+    version: &'static str,
+    environment: Option<&'a str>,
+    #[schemars(with = "Timestamp")]
+    generated: jiff::Timestamp,
+    status: &'static str,
+    errors: Vec<BulletRecord<'a>>,
+    warnings: Vec<BulletRecord<'a>>,
+    notes: Vec<BulletRecord<'a>>,
+    summary: &'a Section<SummaryEntry>,
+    checks: &'a Section<ChecksEntry>,
+    prunes: &'a Section<PruneEntry>,
+}
+
+/// An error, warning or note attributed to its origin
+#[derive(Serialize, JsonSchema)]
+struct BulletRecord<'a> {
+    repository: &'a str,
+    archive_glob: Option<&'a str>,
+    /// The text without the "`repo`\[`archive_glob`\]: " prefix
+    message: &'a str,
+}
+
+/// The messages of the `section` without their prefix, which repeats the origin
+fn messages(section: &Section<BulletPoint>) -> Vec<BulletRecord<'_>> {
+    section
+        .iter()
+        .map(|record| BulletRecord {
+            repository: &record.repository,
+            archive_glob: record.archive_glob.as_deref(),
+            message: record.message(),
+        })
+        .collect()
+}
+
+/// JSON `Formatter` (application/json)
+pub struct Json;
+impl Formatter<Report> for Json {
+    fn format<W>(buf: &mut W, data: &Report) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        let document = Document {
+            schema_version: SCHEMA_VERSION,
+            version: env!("CARGO_PKG_VERSION"),
+            environment: cli::args().environment.as_deref(),
+            generated: jiff::Timestamp::now(),
+            status: data.status().as_str(),
+            errors: messages(&data.errors),
+            warnings: messages(&data.warnings),
+            notes: messages(&data.notes),
+            summary: &data.summary,
+            checks: &data.checks,
+            prunes: &data.prunes,
+        };
+        let json = serde_json::to_string_pretty(&document).map_err(|_| std::fmt::Error)?;
+        writeln!(buf, "{json}")
+    }
+}
+
+/// Print the JSON Schema of the document written by `Json`
+pub(crate) fn print_schema() -> anyhow::Result<()> {
+    let schema = schemars::schema_for!(Document);
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+/// Schema of a timestamp in UTC e.g. `2024-01-02T03:04:05Z`
+pub(crate) struct Timestamp;
+impl JsonSchema for Timestamp {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Timestamp".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({ "type": "string", "format": "date-time" })
+    }
+}
+
+/// A duration in seconds
+pub(crate) fn seconds<S>(duration: &jiff::SignedDuration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// A time of borg in the `BORG_TZ` as timestamp in UTC
+pub(crate) fn timestamp<S>(time: &jiff::civil::DateTime, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    optional_timestamp(&Some(*time), serializer)
+}
+
+/// An optional time of borg in the `BORG_TZ` as timestamp in UTC
+#[allow(clippy::ref_option)] // signature required by `serialize_with`
+pub(crate) fn optional_timestamp<S>(
+    time: &Option<jiff::civil::DateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    time.and_then(|t| t.intz(crate::borg::BORG_TZ).ok())
+        .map(|t| t.timestamp())
+        .serialize(serializer)
+}
+
+/// The exit code of a process or `null` if it was terminated by a signal
+#[allow(clippy::trivially_copy_pass_by_ref)] // signature required by `serialize_with`
+pub(crate) fn exit_code<S>(
+    status: &std::process::ExitStatus,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    status.code().serialize(serializer)
+}
+
+mod tests {
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn document() {
+        use super::{messages, Document, SCHEMA_VERSION};
+        use crate::report::{ChecksEntry, PruneEntry, Report, SummaryEntry};
+        use std::os::unix::process::ExitStatusExt;
+
+        let mut report = Report::new();
+        report.add_error("repo", None, "Failed");
+        report.summary.add((
+            "repo",
+            Some("host-*"),
+            SummaryEntry {
+                archive: "host-2024".to_string(),
+                start: jiff::civil::date(2024, 1, 2).at(3, 4, 5, 0),
+                duration: jiff::SignedDuration::from_millis(1500),
+                original_size: 4_000_000_000_000,
+                ..SummaryEntry::default()
+            },
+        ));
+        report.checks.add((
+            "repo",
+            None::<String>,
+            ChecksEntry {
+                status: std::process::ExitStatus::from_raw(9),
+                timestamp: jiff::Timestamp::UNIX_EPOCH,
+                ..ChecksEntry::default()
+            },
+        ));
+        report.prunes.add((
+            "repo",
+            Some("host-*"),
+            PruneEntry {
+                pruned: 2,
+                ..PruneEntry::default()
+            },
+        ));
+        let document = Document {
+            schema_version: SCHEMA_VERSION,
+            version: "0.0.0",
+            environment: None,
+            generated: jiff::Timestamp::UNIX_EPOCH,
+            status: "critical",
+            errors: messages(&report.errors),
+            warnings: messages(&report.warnings),
+            notes: messages(&report.notes),
+            summary: &report.summary,
+            checks: &report.checks,
+            prunes: &report.prunes,
+        };
+        let json = serde_json::to_value(&document).unwrap();
+
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["generated"], "1970-01-01T00:00:00Z");
+        assert_eq!(json["warnings"], serde_json::json!([]));
+        assert_eq!(
+            json["errors"][0],
+            serde_json::json!({"repository": "repo", "archive_glob": null, "message": "Failed"})
+        );
+
+        let summary = &json["summary"][0];
+        assert_eq!(summary["repository"], "repo");
+        assert_eq!(summary["archive_glob"], "host-*");
+        assert_eq!(summary["archive"], "host-2024");
+        assert_eq!(summary["start"], "2024-01-02T03:04:05Z");
+        assert_eq!(summary["oldest"], serde_json::Value::Null);
+        assert_eq!(summary["duration"], 1.5);
+        assert_eq!(summary["original_size"], 4_000_000_000_000_i64);
+        assert!(summary["original_size"].is_i64());
+
+        // A check terminated by a signal has no exit code
+        let check = &json["checks"][0];
+        assert_eq!(check["repository"], "repo");
+        assert!(check.get("exit_code").unwrap().is_null());
+        assert!(check.get("status").is_none());
+
+        let prune = &json["prunes"][0];
+        assert_eq!(prune["archive_glob"], "host-*");
+        assert_eq!(prune["exit_code"], 0);
+        assert_eq!(prune["pruned"], 2);
+
+        // The schema describes the same fields as the serialization
+        let schema = serde_json::to_value(schemars::schema_for!(Document)).unwrap();
+        let keys = |value: &serde_json::Value| {
+            value
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&schema["properties"]), keys(&json));
+        for (section, record) in [
+            ("errors", "BulletRecord"),
+            ("summary", "SummaryEntryRecord"),
+            ("checks", "ChecksEntryRecord"),
+            ("prunes", "PruneEntryRecord"),
+        ] {
+            assert_eq!(
+                keys(&schema["$defs"][record]["properties"]),
+                keys(&json[section][0])
+            );
+        }
+    }
+}
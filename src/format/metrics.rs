@@ -2,7 +2,11 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use super::Formatter;
-use crate::{borg::BORG_TZ, report::Report};
+use crate::{
+    borg::BORG_TZ,
+    cli,
+    report::{Report, Status},
+};
 use prometheus_client::{
     collector::Collector,
     encoding::{text::encode, DescriptorEncoder, EncodeLabelSet, EncodeMetric},
@@ -13,6 +17,7 @@ use prometheus_client::{
     },
     registry::{Registry, Unit},
 };
+use std::sync::atomic::AtomicU64;
 
 /// A metric label set: `repository`
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
@@ -25,36 +30,86 @@ impl From<String> for RepositoryLabel {
     }
 }
 
-/// A metric label set: `repository`, `hostname` and `archive_glob`
+/// A metric label set: `status`
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct StatusLabel {
+    status: &'static str,
+}
+
+/// A metric label set: `repository` and `mode`
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct EncryptionLabel {
+    repository: String,
+    mode: String,
+}
+impl From<(String, String)> for EncryptionLabel {
+    fn from(value: (String, String)) -> Self {
+        let (repository, mode) = value;
+        Self { repository, mode }
+    }
+}
+
+/// A metric label set: `repository`, `hostname`, `archive_glob` and `tier`
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 struct ArchiveGlobHostnameLabel {
     repository: String,
     hostname: String,
     archive_glob: Option<String>,
+    tier: Option<String>,
 }
-impl From<(String, String, Option<String>)> for ArchiveGlobHostnameLabel {
-    fn from(value: (String, String, Option<String>)) -> Self {
-        let (repository, hostname, archive_glob) = value;
+impl From<(String, String, Option<String>, Option<String>)> for ArchiveGlobHostnameLabel {
+    fn from(value: (String, String, Option<String>, Option<String>)) -> Self {
+        let (repository, hostname, archive_glob, tier) = value;
         Self {
             repository,
             hostname,
             archive_glob,
+            tier,
         }
     }
 }
 
-/// A metric label set: `repository` and `archive_glob`
+/// A metric label set: `repository`, `archive_glob` and `tier`
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
 struct ArchiveGlobLabel {
     repository: String,
     archive_glob: Option<String>,
+    tier: Option<String>,
+}
+impl From<(String, Option<String>, Option<String>)> for ArchiveGlobLabel {
+    fn from(value: (String, Option<String>, Option<String>)) -> Self {
+        let (repository, archive_glob, tier) = value;
+        Self {
+            repository,
+            archive_glob,
+            tier,
+        }
+    }
+}
+
+/// A metric label set: `repository`, `archive_glob`, `tier` and `archive`
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ArchiveLabel {
+    repository: String,
+    archive_glob: Option<String>,
+    tier: Option<String>,
+    archive: String,
 }
-impl From<(String, Option<String>)> for ArchiveGlobLabel {
-    fn from(value: (String, Option<String>)) -> Self {
-        let (repository, archive_glob) = value;
+impl From<(ArchiveGlobLabel, String)> for ArchiveLabel {
+    fn from(value: (ArchiveGlobLabel, String)) -> Self {
+        let (
+            ArchiveGlobLabel {
+                repository,
+                archive_glob,
+                tier,
+            },
+            archive,
+        ) = value;
         Self {
             repository,
             archive_glob,
+            tier,
+            archive,
         }
     }
 }
@@ -70,11 +125,25 @@ fn duration_as_secs(duration: jiff::SignedDuration) -> anyhow::Result<i64> {
         .as_secs())
 }
 
+/// Convert a borg timestamp into a valid non-zero Unix time
+fn unix_time(datetime: jiff::civil::DateTime) -> Option<i64> {
+    datetime
+        .intz(BORG_TZ)
+        .ok()
+        .map(|t| t.timestamp().as_second())
+        .filter(|t| *t > 0)
+}
+
 /// Collect metrics from the `Report` meta structure.
 #[derive(Debug, Default)]
 struct ReportCollector {
     // Repository metrics
     unique_csize: Family<RepositoryLabel, Gauge>,
+    total_size: Family<RepositoryLabel, Gauge>,
+    dedup_ratio: Family<RepositoryLabel, Gauge<f64, AtomicU64>>,
+    savings_ratio: Family<RepositoryLabel, Gauge<f64, AtomicU64>>,
+    growth: Family<RepositoryLabel, Gauge>,
+    encryption_mode: Family<EncryptionLabel, Gauge>,
 
     // Metrics of the last archive (`borg create`)
     create_start_timestamp: Family<ArchiveGlobHostnameLabel, Gauge>,
@@ -83,25 +152,49 @@ struct ReportCollector {
     create_compressed_size: Family<ArchiveGlobHostnameLabel, Gauge>,
     create_deduplicated_size: Family<ArchiveGlobHostnameLabel, Gauge>,
     create_nfiles: Family<ArchiveGlobHostnameLabel, Gauge>,
+    oldest_start_timestamp: Family<ArchiveGlobLabel, Gauge>,
 
     // Metrics of the check of the last archive (`borg check`)
     check_duration: Family<ArchiveGlobLabel, Gauge>,
     check_success: Family<ArchiveGlobLabel, Gauge>,
+    check_archive_timestamp: Family<ArchiveLabel, Gauge>,
+    check_status_changed: Family<ArchiveGlobLabel, Gauge>,
+    check_peak_rss: Family<ArchiveGlobLabel, Gauge>,
+
+    // Metrics of the prune of the repository (`borg prune`)
+    prune_duration: Family<ArchiveGlobLabel, Gauge>,
+    prune_success: Family<ArchiveGlobLabel, Gauge>,
+    prune_pruned_archives: Family<ArchiveGlobLabel, Gauge>,
+    prune_deleted_size: Family<ArchiveGlobLabel, Gauge>,
 }
 
 impl Collector for ReportCollector {
     /// Write annotated metrics into the registry
+    #[allow(clippy::too_many_lines)]
     fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), std::fmt::Error> {
         let Self {
             unique_csize,
+            total_size,
+            dedup_ratio,
+            savings_ratio,
+            growth,
+            encryption_mode,
             create_original_size,
             create_compressed_size,
             create_deduplicated_size,
             create_nfiles,
             create_start_timestamp,
             create_duration,
+            oldest_start_timestamp,
             check_duration,
             check_success,
+            check_archive_timestamp,
+            check_status_changed,
+            check_peak_rss,
+            prune_duration,
+            prune_success,
+            prune_pruned_archives,
+            prune_deleted_size,
         } = self;
 
         /// Encode a metric with the a unit
@@ -121,6 +214,18 @@ impl Collector for ReportCollector {
             Unit::Bytes,
             "Size of the backup repository in bytes (compressed and deduplicated)"
         );
+        register_with_unit!(
+            total_size,
+            "repository_total_size",
+            Unit::Bytes,
+            "Original size of all archives in the backup repository in bytes"
+        );
+        register_with_unit!(
+            growth,
+            "repository_growth",
+            Unit::Bytes,
+            "Growth of the backup repository size since the previous run in bytes"
+        );
         register_with_unit!(
             create_original_size,
             "create_last_original_size",
@@ -151,6 +256,33 @@ impl Collector for ReportCollector {
             Unit::Seconds,
             "Duration of the last backup in seconds"
         );
+        register_with_unit!(
+            oldest_start_timestamp,
+            "repository_oldest_archive_timestamp",
+            Unit::Seconds,
+            "Unix time when the oldest backup was started"
+        );
+
+        dedup_ratio.encode(encoder.encode_descriptor(
+            "repository_dedup_ratio",
+            "Ratio of the original size to the deduplicated size of the backup repository",
+            None,
+            dedup_ratio.metric_type(),
+        )?)?;
+
+        savings_ratio.encode(encoder.encode_descriptor(
+            "repository_savings_ratio",
+            "Share of the original size saved by deduplication and compression of the backup repository",
+            None,
+            savings_ratio.metric_type(),
+        )?)?;
+
+        encryption_mode.encode(encoder.encode_descriptor(
+            "repository_encryption_mode",
+            "Encryption mode of the backup repository",
+            None,
+            encryption_mode.metric_type(),
+        )?)?;
 
         create_nfiles.encode(encoder.encode_descriptor(
             "create_last_files",
@@ -174,6 +306,55 @@ impl Collector for ReportCollector {
             "True (1) if the check of the last archive was successful"
         );
 
+        register_with_unit!(
+            check_archive_timestamp,
+            "check_archive_timestamp",
+            Unit::Seconds,
+            "Unix time of the check of an archive in this run"
+        );
+
+        register_with_unit!(
+            check_peak_rss,
+            "create_check_peak_rss",
+            Unit::Bytes,
+            "Peak memory usage (max RSS) of the check of the last archive"
+        );
+
+        register_with_unit!(
+            check_status_changed,
+            "check_status_changed",
+            boolean,
+            "True (1) if the outcome of the check changed since the previous check"
+        );
+
+        register_with_unit!(
+            prune_duration,
+            "prune_last_duration",
+            Unit::Seconds,
+            "Duration of the prune of the repository in seconds"
+        );
+
+        register_with_unit!(
+            prune_success,
+            "prune_last_success",
+            boolean,
+            "True (1) if the prune of the repository was successful"
+        );
+
+        prune_pruned_archives.encode(encoder.encode_descriptor(
+            "prune_last_pruned_archives",
+            "Number of archives pruned from the repository",
+            None,
+            prune_pruned_archives.metric_type(),
+        )?)?;
+
+        register_with_unit!(
+            prune_deleted_size,
+            "prune_last_deleted_size",
+            Unit::Bytes,
+            "Deduplicated size of the data deleted by the prune of the repository in bytes"
+        );
+
         Ok(())
     }
 }
@@ -183,17 +364,31 @@ impl From<&Report> for ReportCollector {
     ///
     /// A `Report` is a representation for humans. Empty data (or a value of 0)
     /// in the `Report` can translate to no actual measurement (no metric).
+    #[allow(clippy::too_many_lines)]
     fn from(report: &Report) -> Self {
         let Self {
             unique_csize,
+            total_size,
+            dedup_ratio,
+            savings_ratio,
+            growth,
+            encryption_mode,
             create_original_size,
             create_compressed_size,
             create_deduplicated_size,
             create_nfiles,
             create_start_timestamp,
             create_duration,
+            oldest_start_timestamp,
             check_duration,
             check_success,
+            check_archive_timestamp,
+            check_status_changed,
+            check_peak_rss,
+            prune_duration,
+            prune_success,
+            prune_pruned_archives,
+            prune_deleted_size,
         } = Self::default();
 
         // Process the summary table.
@@ -202,13 +397,35 @@ impl From<&Report> for ReportCollector {
             let archive_label = &ArchiveGlobHostnameLabel::from((
                 archive.repository.clone(),
                 archive.hostname.clone(),
-                archive.archive_glob.clone(),
+                report.archive_glob_label(&archive.repository, archive.archive_glob.as_deref()),
+                archive.tier.clone(),
             ));
 
             // Ok: The size of the repo can be zero.
             unique_csize
                 .get_or_create(repository_label)
                 .set(archive.unique_csize);
+            total_size
+                .get_or_create(repository_label)
+                .set(archive.total_size);
+            if let Some(value) = archive.dedup_ratio() {
+                dedup_ratio.get_or_create(repository_label).set(value);
+            }
+            if let Some(value) = archive.savings() {
+                savings_ratio.get_or_create(repository_label).set(value);
+            }
+            if let Some(value) = archive.growth {
+                growth.get_or_create(repository_label).set(value);
+            }
+            // The encryption mode is unknown, when `borg info` failed.
+            if !archive.encryption.is_empty() {
+                encryption_mode
+                    .get_or_create(&EncryptionLabel::from((
+                        archive.repository.clone(),
+                        archive.encryption.clone(),
+                    )))
+                    .set(1);
+            }
 
             // Skip all entries without an archive name since there was no last archive created.
             if !&archive.archive.is_empty() {
@@ -226,13 +443,7 @@ impl From<&Report> for ReportCollector {
                     .set(archive.nfiles);
 
                 // Only create a `last_start_timestamp` if it is a valid non-zero Unix time
-                if let Some(start) = archive
-                    .start
-                    .intz(BORG_TZ)
-                    .ok()
-                    .map(|t| t.timestamp().as_second())
-                    .filter(|t| *t > 0)
-                {
+                if let Some(start) = unix_time(archive.start) {
                     create_start_timestamp
                         .get_or_create(archive_label)
                         .set(start);
@@ -241,13 +452,29 @@ impl From<&Report> for ReportCollector {
                 if let Ok(duration) = duration_as_secs(archive.duration) {
                     create_duration.get_or_create(archive_label).set(duration);
                 }
+
+                if let Some(oldest) = archive.oldest.and_then(unix_time) {
+                    oldest_start_timestamp
+                        .get_or_create(&ArchiveGlobLabel::from((
+                            archive.repository.clone(),
+                            report.archive_glob_label(
+                                &archive.repository,
+                                archive.archive_glob.as_deref(),
+                            ),
+                            archive.tier.clone(),
+                        )))
+                        .set(oldest);
+                }
             }
         }
 
         // Process `borg check` results
         for check in &*report.checks {
-            let archive_label =
-                &ArchiveGlobLabel::from((check.repository.clone(), check.archive_glob.clone()));
+            let archive_label = &ArchiveGlobLabel::from((
+                check.repository.clone(),
+                report.archive_glob_label(&check.repository, check.archive_glob.as_deref()),
+                report.archive_tier(&check.repository, check.archive_glob.as_deref()),
+            ));
 
             if let Ok(duration_secs) = duration_as_secs(check.duration) {
                 check_duration
@@ -258,18 +485,77 @@ impl From<&Report> for ReportCollector {
             check_success
                 .get_or_create(archive_label)
                 .set(check.status.success().into());
+
+            if cli::args().show_check_changes {
+                check_status_changed
+                    .get_or_create(archive_label)
+                    .set(check.change.is_some().into());
+            }
+
+            if let Some(peak_rss) = check.peak_rss {
+                check_peak_rss
+                    .get_or_create(archive_label)
+                    .set(i64::try_from(peak_rss).unwrap_or(i64::MAX));
+            }
+
+            if let Some(archive_name) = &check.archive_name {
+                check_archive_timestamp
+                    .get_or_create(&ArchiveLabel::from((
+                        archive_label.clone(),
+                        archive_name.clone(),
+                    )))
+                    .set(check.timestamp.as_second());
+            }
+        }
+
+        // Process `borg prune` results
+        for prune in &*report.prunes {
+            let archive_label = &ArchiveGlobLabel::from((
+                prune.repository.clone(),
+                report.archive_glob_label(&prune.repository, prune.archive_glob.as_deref()),
+                report.archive_tier(&prune.repository, prune.archive_glob.as_deref()),
+            ));
+            if let Ok(duration_secs) = duration_as_secs(prune.duration) {
+                prune_duration
+                    .get_or_create(archive_label)
+                    .set(duration_secs);
+            }
+            prune_success
+                .get_or_create(archive_label)
+                .set(prune.status.success().into());
+            prune_pruned_archives
+                .get_or_create(archive_label)
+                .set(i64::try_from(prune.pruned).unwrap_or(i64::MAX));
+            if let Some(deleted_size) = prune.deleted_size {
+                prune_deleted_size
+                    .get_or_create(archive_label)
+                    .set(deleted_size.abs());
+            }
         }
 
         Self {
             unique_csize,
+            total_size,
+            dedup_ratio,
+            savings_ratio,
+            growth,
+            encryption_mode,
             create_start_timestamp,
             create_duration,
             create_original_size,
             create_compressed_size,
             create_deduplicated_size,
             create_nfiles,
+            oldest_start_timestamp,
             check_duration,
             check_success,
+            check_archive_timestamp,
+            check_status_changed,
+            check_peak_rss,
+            prune_duration,
+            prune_success,
+            prune_pruned_archives,
+            prune_deleted_size,
         }
     }
 }
@@ -282,28 +568,113 @@ impl Formatter<Report> for Metrics {
         W: std::fmt::Write,
     {
         let mut registry = <Registry>::default();
+        let prefix = &cli::args().metrics_prefix;
+        // The prefix replaces `borg` of `borgreport` in the name of the own metrics
+        let report_prefix = format!("{prefix}report");
 
         //borgreport info metadata and generated at timestamp
-        registry.register(
-            env!("CARGO_PKG_NAME"),
-            "borgreport metadata",
-            Info::new([
-                ("name", env!("CARGO_PKG_NAME")),
-                ("version", env!("CARGO_PKG_VERSION")),
-            ]),
-        );
+        let mut info = vec![
+            ("name", env!("CARGO_PKG_NAME")),
+            ("version", env!("CARGO_PKG_VERSION")),
+        ];
+        if let Some(environment) = &cli::args().environment {
+            info.push(("environment", environment));
+        }
+        registry.register(&report_prefix, "borgreport metadata", Info::new(info));
         registry.register_with_unit(
-            concat!(env!("CARGO_PKG_NAME"), "_last_report_timestamp"),
+            format!("{report_prefix}_last_report_timestamp"),
             "Unix time when the metrics were generated",
             Unit::Seconds,
             ConstGauge::new(jiff::Timestamp::now().as_second()),
         );
 
-        // Collect metrics from the `Report`
-        let borg_registry = registry.sub_registry_with_prefix("borg");
+        // Aggregated status of all repositories: the active state is set to 1
+        let status = Family::<StatusLabel, Gauge>::default();
+        for state in Status::ALL {
+            status
+                .get_or_create(&StatusLabel {
+                    status: state.as_str(),
+                })
+                .set((state == report.status()).into());
+        }
+        registry.register(
+            format!("{report_prefix}_status"),
+            "Aggregated status of all repositories (ok, degraded or critical)",
+            status,
+        );
+
+        // Collect metrics from the `Report` (in the flat layout without the prefix)
+        let borg_registry = if cli::args().metrics_flat {
+            &mut registry
+        } else {
+            registry.sub_registry_with_prefix(prefix)
+        };
+
+        // Time of the last successful check per repository (from the state)
+        let check_last_success = Family::<RepositoryLabel, Gauge>::default();
+        for (repository, timestamp) in &report.last_check_success {
+            check_last_success
+                .get_or_create(&RepositoryLabel::from(repository.clone()))
+                .set(timestamp.as_second());
+        }
+        borg_registry.register_with_unit(
+            "check_last_success_timestamp",
+            "Unix time of the last successful check known in the state",
+            Unit::Seconds,
+            check_last_success,
+        );
+
+        // Available space of the filesystem of each local repository
+        let free_space = Family::<RepositoryLabel, Gauge>::default();
+        for (repository, available) in &report.free_space {
+            free_space
+                .get_or_create(&RepositoryLabel::from(repository.clone()))
+                .set(i64::try_from(*available).unwrap_or(i64::MAX));
+        }
+        borg_registry.register_with_unit(
+            "repository_free_size",
+            "Available space on the filesystem of a local repository",
+            Unit::Bytes,
+            free_space,
+        );
+
+        // Storage quota and its usage of each repository with a quota
+        let quota = Family::<RepositoryLabel, Gauge>::default();
+        let quota_used = Family::<RepositoryLabel, Gauge>::default();
+        for (repository, (size, used)) in &report.quotas {
+            let label = RepositoryLabel::from(repository.clone());
+            quota
+                .get_or_create(&label)
+                .set(i64::try_from(*size).unwrap_or(i64::MAX));
+            quota_used.get_or_create(&label).set(*used);
+        }
+        borg_registry.register_with_unit(
+            "repository_quota",
+            "Storage quota of the repository",
+            Unit::Bytes,
+            quota,
+        );
+        borg_registry.register_with_unit(
+            "repository_quota_used",
+            "Deduplicated compressed size of the repository counted against its storage quota",
+            Unit::Bytes,
+            quota_used,
+        );
         borg_registry.register_collector(Box::new(ReportCollector::from(report)));
 
-        encode(buf, &registry)?;
+        // Drop the metadata except the TYPE lines in the minimal format
+        if cli::args().metrics_minimal {
+            let mut metrics = String::new();
+            encode(&mut metrics, &registry)?;
+            for line in metrics
+                .lines()
+                .filter(|line| !line.starts_with("# HELP ") && !line.starts_with("# UNIT "))
+            {
+                writeln!(buf, "{line}")?;
+            }
+        } else {
+            encode(buf, &registry)?;
+        }
         Ok(())
     }
 }
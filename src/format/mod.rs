@@ -2,15 +2,75 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 mod html;
+pub(crate) mod json;
 mod metrics;
+mod oneline;
 mod text;
 
-use crate::report::Component;
+use crate::{cli, report::Component};
 
 pub(crate) use html::Html;
+pub(crate) use json::Json;
 pub(crate) use metrics::Metrics;
+pub(crate) use oneline::Oneline;
 pub(crate) use text::Text;
 
+/// Prefix of the report titles with the `--environment` e.g. `[prod] `
+pub(crate) fn environment_prefix() -> String {
+    cli::args()
+        .environment
+        .as_ref()
+        .map(|environment| format!("[{environment}] "))
+        .unwrap_or_default()
+}
+
+/// Format a signed change in bytes e.g. `+1.2kB`
+pub(crate) fn human_delta_bytes(delta: i64) -> String {
+    use human_repr::HumanCount;
+    let sign = if delta < 0 { '-' } else { '+' };
+    format!("{sign}{}", delta.unsigned_abs().human_count_bytes())
+}
+
+/// Format a ratio as percentage with one decimal e.g. `87.5%`
+pub(crate) fn format_percent(ratio: f64) -> String {
+    format!("{:.1}%", ratio * 100.0)
+}
+
+/// Truncate the `text` to `width` chars and mark the cut with `…`
+pub(crate) fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() > width {
+        let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+        truncated.push('…');
+        truncated
+    } else {
+        text.to_string()
+    }
+}
+
+/// Maximum width of a command line in the summary
+pub(crate) const COMMAND_WIDTH: usize = 60;
+
+/// Format a duration with a consistent precision:
+/// Milliseconds below a second, tenths of a second below a minute and whole seconds above e.g. `1h02m03s`
+pub(crate) fn human_duration(duration: jiff::SignedDuration) -> String {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let millis = (duration.as_secs_f64() * 1000.0).round().max(0.0) as u64;
+    if millis < 1000 {
+        return format!("{millis}ms");
+    }
+    let tenths = (millis + 50) / 100;
+    if tenths < 600 {
+        return format!("{}.{}s", tenths / 10, tenths % 10);
+    }
+    let secs = (millis + 500) / 1000;
+    let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h{mins:02}m{secs:02}s")
+    } else {
+        format!("{mins}m{secs:02}s")
+    }
+}
+
 /// Format a `T` with the `Formatter`
 pub trait Formatter<T>
 where
@@ -46,3 +106,34 @@ pub trait Formattable: Sized + Component {
 
 /// All `ReportComponent`s can be formatted
 impl<T> Formattable for T where T: Sized + Component {}
+
+mod tests {
+    #[test]
+    fn truncate() {
+        use super::truncate;
+
+        assert_eq!(truncate("borg create ::a /etc", 20), "borg create ::a /etc");
+        assert_eq!(
+            truncate("borg create ::a /etc /srv", 20),
+            "borg create ::a /et…"
+        );
+        assert_eq!(truncate("", 0), "");
+    }
+
+    #[test]
+    fn human_duration() {
+        use super::human_duration;
+        use jiff::SignedDuration;
+
+        assert_eq!(human_duration(SignedDuration::ZERO), "0ms");
+        assert_eq!(human_duration(SignedDuration::from_nanos(400_000)), "0ms");
+        assert_eq!(human_duration(SignedDuration::from_millis(999)), "999ms");
+        assert_eq!(human_duration(SignedDuration::from_micros(999_600)), "1.0s");
+        assert_eq!(human_duration(SignedDuration::from_millis(1_500)), "1.5s");
+        assert_eq!(human_duration(SignedDuration::from_millis(59_940)), "59.9s");
+        assert_eq!(human_duration(SignedDuration::from_millis(59_960)), "1m00s");
+        assert_eq!(human_duration(SignedDuration::from_secs(61)), "1m01s");
+        assert_eq!(human_duration(SignedDuration::from_secs(3_661)), "1h01m01s");
+        assert_eq!(human_duration(SignedDuration::from_secs(-5)), "0ms");
+    }
+}
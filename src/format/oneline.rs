@@ -0,0 +1,32 @@
+// SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::Formatter;
+use crate::report::Report;
+use human_repr::HumanCount;
+
+/// One-line `Formatter` for a status bar e.g. `borg: 12 ok, 1 warn, 0 err (1.2TB)`
+pub struct Oneline;
+impl Formatter<Report> for Oneline {
+    fn format<W>(buf: &mut W, data: &Report) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        // Each repository is counted once by its worst state
+        let (mut ok, mut warn, mut err) = (0, 0, 0);
+        for name in data.repositories.keys() {
+            if data.has_errors_for(name) {
+                err += 1;
+            } else if data.has_warnings_for(name) {
+                warn += 1;
+            } else {
+                ok += 1;
+            }
+        }
+        writeln!(
+            buf,
+            "borg: {ok} ok, {warn} warn, {err} err ({})",
+            data.total_unique_csize().human_count_bytes()
+        )
+    }
+}
@@ -1,10 +1,20 @@
 // SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use super::{Formattable, Formatter};
-use crate::report::{BulletPoint, ChecksEntry, Report, Section, SummaryEntry};
+use super::{
+    format_percent, human_delta_bytes, human_duration, truncate, Formattable, Formatter,
+    COMMAND_WIDTH,
+};
+use crate::{
+    cli::{self, Rollup},
+    digest::Digest,
+    report::{
+        BulletPoint, CheckChange, ChecksEntry, HostRollup, PruneEntry, Report, Section,
+        SummaryEntry,
+    },
+};
 use comfy_table::{presets::ASCII_MARKDOWN, CellAlignment, ContentArrangement, Table};
-use human_repr::{HumanCount, HumanDuration};
+use human_repr::HumanCount;
 
 /// Text `Formatter` (text/plain)
 pub struct Text;
@@ -18,7 +28,8 @@ impl Formatter<Report> for Text {
         // Title
         writeln!(
             buf,
-            "==== Backup report ({}) ====\n",
+            "==== {}Backup report ({}) ====\n",
+            super::environment_prefix(),
             jiff::fmt::strtime::format("%F", &now).unwrap_or_default(),
         )?;
 
@@ -32,8 +43,20 @@ impl Formatter<Report> for Text {
                 data.warnings.to_string(Self)?
             )?;
         }
-        if !data.summary.is_empty() {
-            writeln!(buf, "=== Summary ===\n\n{}", data.summary.to_string(Self)?)?;
+        let healthy = data.healthy_repositories();
+        if cli::args().show_ok && !healthy.is_empty() {
+            writeln!(buf, "=== OK ===\n\n{}\n", healthy.join(", "))?;
+        }
+        let summary = data.displayed_summary();
+        if !summary.is_empty() {
+            writeln!(buf, "=== Summary ===\n\n{}", summary.to_string(Self)?)?;
+        }
+        if cli::args().rollup == Some(Rollup::Host) && !data.summary.is_empty() {
+            writeln!(
+                buf,
+                "=== Summary by host ===\n\n{}",
+                data.summary.rollup_by_host().to_string(Self)?
+            )?;
         }
         if !data.checks.is_empty() {
             writeln!(
@@ -42,8 +65,29 @@ impl Formatter<Report> for Text {
                 data.checks.to_string(Self)?,
             )?;
         }
+        if !data.prunes.is_empty() {
+            writeln!(
+                buf,
+                "=== `borg prune` result ===\n\n{}",
+                data.prunes.to_string(Self)?,
+            )?;
+        }
+        if !data.notes.is_empty() {
+            writeln!(buf, "=== Notes ===\n\n{}", data.notes.to_string(Self)?)?;
+        }
 
         // Footer
+        if let Some((total_size, unique_csize, ratio)) =
+            data.savings().filter(|_| cli::args().show_savings)
+        {
+            writeln!(
+                buf,
+                "Savings: {} of {} source stored as {}\n",
+                format_percent(ratio),
+                total_size.human_count_bytes(),
+                unique_csize.human_count_bytes()
+            )?;
+        }
         writeln!(
             buf,
             "Generated {} ({} {})",
@@ -60,14 +104,24 @@ impl Formatter<Section<BulletPoint>> for Text {
         W: std::fmt::Write,
     {
         // Print all lines of the section entry and add a bullet point to its first line
+        // Fold the entry after `error_lines` (if given and not disabled by `no_truncate`)
+        let max_lines = cli::args()
+            .error_lines
+            .filter(|_| !cli::args().no_truncate)
+            .unwrap_or(usize::MAX);
         for entry in data.dedup_inner() {
             let mut lines = entry.trim().lines();
             if let Some(line) = lines.next() {
                 writeln!(buf, " * {line}")?;
             }
-            for line in lines {
+            for line in lines.by_ref().take(max_lines - 1) {
                 writeln!(buf, "   {line}")?;
             }
+            match lines.count() {
+                0 => {}
+                1 => writeln!(buf, "   … (1 more line)")?,
+                omitted => writeln!(buf, "   … ({omitted} more lines)")?,
+            }
         }
         Ok(())
     }
@@ -75,6 +129,115 @@ impl Formatter<Section<BulletPoint>> for Text {
 
 impl Formatter<Section<SummaryEntry>> for Text {
     fn format<W>(buf: &mut W, data: &Section<SummaryEntry>) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        let show_encryption = cli::args().show_encryption;
+        let show_command = cli::args().show_command;
+        let show_comment = cli::args().show_comment;
+        let show_savings = cli::args().show_savings;
+        let show_growth = data.iter().any(|e| e.growth.is_some());
+        let show_delta = data.iter().any(|e| e.original_size_delta.is_some());
+        let show_tier = data.iter().any(|e| e.tier.is_some());
+        let mut header = vec!["Repository"];
+        if show_tier {
+            header.push("Tier");
+        }
+        header.extend([
+            "Hostname",
+            "Last archive",
+            "Start",
+            "Oldest",
+            "Duration",
+            "Source",
+            "Δ Archive",
+            "∑ Source",
+            "∑ Repository",
+            "Dedup",
+        ]);
+        if show_growth {
+            header.push("Δ Repository");
+        }
+        if show_delta {
+            header.push("Δ Source");
+        }
+        if show_savings {
+            header.push("Savings");
+        }
+        if show_encryption {
+            header.push("Encryption");
+        }
+        if show_command {
+            header.push("Command");
+        }
+        if show_comment {
+            header.push("Comment");
+        }
+        let mut table = Table::new();
+        table
+            .load_preset(ASCII_MARKDOWN)
+            .set_content_arrangement(ContentArrangement::Disabled)
+            .set_header(header);
+        for e in data.inner() {
+            let mut row = vec![format!("{}", e.repository)];
+            if show_tier {
+                row.push(e.tier.clone().unwrap_or_default());
+            }
+            row.extend([
+                e.hostname.clone(),
+                e.archive.clone(),
+                jiff::fmt::strtime::format("%F", e.start).unwrap_or_else(|_| String::default()),
+                e.oldest
+                    .and_then(|t| jiff::fmt::strtime::format("%F", t).ok())
+                    .unwrap_or_default(),
+                human_duration(e.duration),
+                format!("{}", e.original_size.human_count_bytes()),
+                format!("{}", e.deduplicated_size.human_count_bytes()),
+                format!("{}", e.total_size.human_count_bytes()),
+                format!("{}", e.unique_csize.human_count_bytes()),
+                e.dedup_ratio()
+                    .map(|r| format!("{r:.1}x"))
+                    .unwrap_or_default(),
+            ]);
+            if show_growth {
+                row.push(e.growth.map(human_delta_bytes).unwrap_or_default());
+            }
+            if show_delta {
+                row.push(
+                    e.original_size_delta
+                        .map(human_delta_bytes)
+                        .unwrap_or_default(),
+                );
+            }
+            if show_savings {
+                row.push(e.savings().map(format_percent).unwrap_or_default());
+            }
+            if show_encryption {
+                row.push(e.encryption.clone());
+            }
+            if show_command {
+                row.push(truncate(&e.command_line, COMMAND_WIDTH));
+            }
+            if show_comment {
+                row.push(truncate(&e.comment, COMMAND_WIDTH));
+            }
+            table.add_row(row);
+        }
+        //the columns 5 to 10 (and the deltas and savings) are aligned right (shifted by the tier column)
+        let offset = usize::from(show_tier);
+        let optional =
+            usize::from(show_growth) + usize::from(show_delta) + usize::from(show_savings);
+        for i in 5 + offset..=offset + 10 + optional {
+            if let Some(c) = table.column_mut(i) {
+                c.set_cell_alignment(CellAlignment::Right);
+            }
+        }
+        writeln!(buf, "{table}")
+    }
+}
+
+impl Formatter<Section<PruneEntry>> for Text {
+    fn format<W>(buf: &mut W, data: &Section<PruneEntry>) -> std::fmt::Result
     where
         W: std::fmt::Write,
     {
@@ -84,28 +247,65 @@ impl Formatter<Section<SummaryEntry>> for Text {
             .set_content_arrangement(ContentArrangement::Disabled)
             .set_header(vec![
                 "Repository",
-                "Hostname",
-                "Last archive",
-                "Start",
+                "Glob",
                 "Duration",
+                "Okay",
+                "Pruned",
+                "Kept",
+                "Deleted",
+            ]);
+        for e in data.inner() {
+            table.add_row(vec![
+                format!("{}", e.repository),
+                e.archive_glob.clone().unwrap_or_default(),
+                human_duration(e.duration),
+                format!("{}", if e.status.success() { "yes" } else { "no" }),
+                format!("{}", e.pruned),
+                format!("{}", e.kept),
+                e.deleted_size
+                    .map(|size| size.unsigned_abs().human_count_bytes().to_string())
+                    .unwrap_or_default(),
+            ]);
+        }
+        //columns 2 to 6 are aligned right
+        for i in 2..=6 {
+            if let Some(c) = table.column_mut(i) {
+                c.set_cell_alignment(CellAlignment::Right);
+            }
+        }
+        writeln!(buf, "{table}")
+    }
+}
+
+impl Formatter<HostRollup> for Text {
+    fn format<W>(buf: &mut W, data: &HostRollup) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        let mut table = Table::new();
+        table
+            .load_preset(ASCII_MARKDOWN)
+            .set_content_arrangement(ContentArrangement::Disabled)
+            .set_header(vec![
+                "Hostname",
+                "Repositories",
+                "Archives",
                 "Source",
                 "Δ Archive",
                 "∑ Repository",
             ]);
-        for e in data.inner() {
+        for e in &data.0 {
             table.add_row(vec![
-                format!("{}", e.repository),
                 format!("{}", e.hostname),
-                format!("{}", e.archive),
-                jiff::fmt::strtime::format("%F", e.start).unwrap_or_else(|_| String::default()),
-                format!("{}", e.duration.as_secs_f64().human_duration()),
+                format!("{}", e.repositories),
+                format!("{}", e.archives),
                 format!("{}", e.original_size.human_count_bytes()),
                 format!("{}", e.deduplicated_size.human_count_bytes()),
                 format!("{}", e.unique_csize.human_count_bytes()),
             ]);
         }
-        //the columns 4,5,6,7 are aligned right
-        for i in 4..=7 {
+        //the columns 1,2,3,4,5 are aligned right
+        for i in 1..=5 {
             if let Some(c) = table.column_mut(i) {
                 c.set_cell_alignment(CellAlignment::Right);
             }
@@ -119,21 +319,53 @@ impl Formatter<Section<ChecksEntry>> for Text {
     where
         W: std::fmt::Write,
     {
+        let show_exit_codes = cli::args().show_exit_codes;
+        let show_changes = cli::args().show_check_changes;
+        let show_resources = cli::args().show_resources;
+        let mut header = vec!["Repository", "Archive", "Duration", "Okay"];
+        if show_exit_codes {
+            header.push("Exit code");
+        }
+        if show_resources {
+            header.push("Memory");
+        }
+        if show_changes {
+            header.push("Change");
+        }
         let mut table = Table::new();
         table
             .load_preset(ASCII_MARKDOWN)
             .set_content_arrangement(ContentArrangement::Disabled)
-            .set_header(vec!["Repository", "Archive", "Duration", "Okay"]);
+            .set_header(header);
         for e in data.inner() {
-            table.add_row(vec![
+            let mut row = vec![
                 format!("{}", e.repository),
                 format!("{}", e.archive_name.clone().unwrap_or_default()),
-                format!("{}", e.duration.as_secs_f64().human_duration()),
+                human_duration(e.duration),
                 format!("{}", if e.status.success() { "yes" } else { "no" }),
-            ]);
+            ];
+            if show_exit_codes {
+                row.push(e.exit_code());
+            }
+            if show_resources {
+                row.push(
+                    e.peak_rss
+                        .map(|b| b.human_count_bytes().to_string())
+                        .unwrap_or_default(),
+                );
+            }
+            if show_changes {
+                row.push(
+                    e.change
+                        .map(CheckChange::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                );
+            }
+            table.add_row(row);
         }
-        //columns 2,3 are aligned right
-        for i in 2..=3 {
+        //columns 2,3 (and the exit code and memory) are aligned right
+        for i in 2..=3 + usize::from(show_exit_codes) + usize::from(show_resources) {
             if let Some(c) = table.column_mut(i) {
                 c.set_cell_alignment(CellAlignment::Right);
             }
@@ -141,3 +373,71 @@ impl Formatter<Section<ChecksEntry>> for Text {
         writeln!(buf, "{table}")
     }
 }
+
+impl Formatter<Digest> for Text {
+    fn format<W>(buf: &mut W, data: &Digest) -> std::fmt::Result
+    where
+        W: std::fmt::Write,
+    {
+        let now = jiff::Zoned::now();
+
+        // Title
+        writeln!(
+            buf,
+            "==== Backup digest {} ({} - {}) ====\n",
+            data.period.as_str(),
+            jiff::fmt::strtime::format("%F", data.since).unwrap_or_default(),
+            jiff::fmt::strtime::format("%F", &now).unwrap_or_default(),
+        )?;
+
+        let attention: Vec<String> = data
+            .entries
+            .iter()
+            .filter_map(|e| e.attention().map(|a| format!(" * {}: {a}", e.repository)))
+            .collect();
+        if !attention.is_empty() {
+            writeln!(buf, "=== Attention ===\n\n{}\n", attention.join("\n"))?;
+        }
+
+        let mut table = Table::new();
+        table
+            .load_preset(ASCII_MARKDOWN)
+            .set_content_arrangement(ContentArrangement::Disabled)
+            .set_header(vec![
+                "Repository",
+                "Runs",
+                "Errors",
+                "Warnings",
+                "Backups",
+                "∑ Repository",
+                "Δ Period",
+            ]);
+        for e in &data.entries {
+            table.add_row(vec![
+                e.repository.clone(),
+                e.runs.to_string(),
+                e.failed.to_string(),
+                e.warned.to_string(),
+                e.backups.to_string(),
+                format!("{}", e.unique_csize.human_count_bytes()),
+                human_delta_bytes(e.growth),
+            ]);
+        }
+        //the columns 1 to 6 are aligned right
+        for i in 1..=6 {
+            if let Some(c) = table.column_mut(i) {
+                c.set_cell_alignment(CellAlignment::Right);
+            }
+        }
+        writeln!(buf, "=== Summary ===\n\n{table}\n")?;
+
+        // Footer
+        writeln!(
+            buf,
+            "Generated {} ({} {})",
+            jiff::fmt::rfc2822::to_string(&now).unwrap_or_default(),
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )
+    }
+}
@@ -9,52 +9,94 @@
 )]
 #![warn(clippy::pedantic)]
 
-use std::{io::IsTerminal, path::PathBuf};
+use std::{
+    io::IsTerminal,
+    path::PathBuf,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 
 use borg::Borg;
 
-use report::{Formattable, Report};
+use report::{CheckChange, Formattable, Report};
 use repository::Repository;
-use utils::send_mail;
+use state::State;
+use utils::{send_mail, write_file};
 
+mod anomaly;
 mod borg;
 mod borg_json;
 mod cli;
+mod digest;
 mod format;
 mod report;
 mod repository;
+mod state;
 mod utils;
 
+/// Minimum interval between two status updates emitted to systemd
+const NOTIFY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
 /// Emit status information to the caller
-/// - If a terminal is attached, print a message and return the cursor to the begin of line.
-///   The message gets whitespace filled and truncated at 76 chars.
-/// - If `NOTIFY_SOCKET` is set, emit the message to systemd
+/// If a terminal is attached, print a message and return the cursor to the begin of line.
+/// The message gets whitespace filled and truncated at 76 chars.
 fn emit_progress<T: AsRef<str>>(msg: T) {
-    if !cli::args().no_progress {
-        // Emit to console, if a terminal is attached
-        if std::io::stdin().is_terminal() {
-            eprint!("{:<76.76}\r", msg.as_ref());
-        }
+    // Emit to console, if a terminal is attached
+    let args = cli::args();
+    if !(args.no_progress || args.fast) && std::io::stdin().is_terminal() {
+        eprint!("{:<76.76}\r", msg.as_ref());
+    }
+}
 
-        // Emit status to systemd, if env NOTIFY_SOCKET is set (and any discard errors)
-        let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Status(msg.as_ref())]);
+/// Emit the aggregated progress to systemd, if `NOTIFY_SOCKET` is set.
+/// Updates are throttled to one per `NOTIFY_INTERVAL`, except for the final one.
+fn notify_progress(processed: usize, total: usize) {
+    notify_status(
+        &format!("Processed {processed}/{total} repositories"),
+        processed == total,
+    );
+}
+
+/// Emit a `status` to systemd, if `NOTIFY_SOCKET` is set.
+/// Updates are throttled to one per `NOTIFY_INTERVAL`, unless `force` is set.
+fn notify_status(status: &str, force: bool) {
+    static LAST_NOTIFY: std::sync::Mutex<Option<std::time::Instant>> = std::sync::Mutex::new(None);
+
+    if cli::args().no_progress || cli::args().fast {
+        return;
+    }
+    let Ok(mut last_notify) = LAST_NOTIFY.lock() else {
+        return;
+    };
+    if !force && last_notify.is_some_and(|t| t.elapsed() < NOTIFY_INTERVAL) {
+        return;
     }
+    *last_notify = Some(std::time::Instant::now());
+
+    // Discard any errors
+    let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Status(status)]);
 }
 
-/// Collect all *.env files from given directories and return them sorted
-fn collect_env_files<'a>(env_dirs: impl IntoIterator<Item = &'a PathBuf>) -> Result<Vec<PathBuf>> {
+/// Collect all files with the `extension` e.g. "env" from given directories and return them sorted
+fn collect_files<'a>(
+    dirs: impl IntoIterator<Item = &'a PathBuf>,
+    extension: &str,
+) -> Result<Vec<PathBuf>> {
     let mut files: Vec<PathBuf> = Vec::new();
-    for env_dir in env_dirs {
+    for dir in dirs {
         files.extend(
-            std::fs::read_dir(env_dir)
-                .context(format!("Cannot open env directory: {env_dir:?}"))?
+            std::fs::read_dir(dir)
+                .context(format!("Cannot open {extension} directory: {dir:?}"))?
                 .filter_map(std::result::Result::ok)
                 .filter_map(|entry| entry.path().is_file().then_some(entry.path()))
                 .filter(|path| {
                     path.extension()
-                        .is_some_and(|ext| ext.eq_ignore_ascii_case("env"))
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
                 }),
         );
     }
@@ -62,10 +104,170 @@ fn collect_env_files<'a>(env_dirs: impl IntoIterator<Item = &'a PathBuf>) -> Res
     Ok(files)
 }
 
+/// Find the repository names used more than once e.g. `a/prod.env` and `b/prod.env`
+fn duplicate_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut seen = std::collections::BTreeSet::new();
+    let mut duplicates: Vec<&str> = names.into_iter().filter(|n| !seen.insert(*n)).collect();
+    duplicates.sort_unstable();
+    duplicates.dedup();
+    duplicates
+}
+
+/// Run `borg check` on the selected archives: The newest archive or a sample of random archives
+fn check_report(
+    repo: &Repository,
+    borg: &Borg,
+    archive_glob: Option<&str>,
+    info: &borg::Info,
+    state: Option<&mut State>,
+) -> Report {
+    // Check the whole repository, when there are no archives found (and no glob was given initially)
+    // -> An empty repository can also be checked.
+    if info.archives.is_empty() {
+        return Report::from_borg_check_result(
+            repo,
+            archive_glob,
+            None,
+            &borg.check(None, &repo.check_options),
+        );
+    }
+
+    let mut report = Report::new();
+    let archives = match repo.check_sample {
+        Some(n) => match borg.list(archive_glob) {
+            Ok(list) => {
+                let names: Vec<String> = list.archives.into_iter().map(|a| a.name).collect();
+                // Without a state each sample is independent
+                let mut scratch = State::default();
+                state
+                    .unwrap_or(&mut scratch)
+                    .sample_archives(archive_glob, &names, n)
+            }
+            Err(e) => {
+                report.add_error(&repo.name, archive_glob, e.to_string());
+                Vec::new()
+            }
+        },
+        None => info
+            .archives
+            .last()
+            .map(|a| a.name.clone())
+            .into_iter()
+            .collect(),
+    };
+    for archive in &archives {
+        report.append(Report::from_borg_check_result(
+            repo,
+            archive_glob,
+            Some(archive),
+            &borg.check(Some(archive), &repo.check_options),
+        ));
+    }
+    report
+}
+
+/// True if `borg check` runs today. The check runs only on the days of its schedule.
+fn check_due(repo: &Repository, report: &mut Report) -> bool {
+    let today = jiff::Zoned::now().date();
+    match &repo.check_schedule {
+        Some(schedule) if repo.run_check && !schedule.is_due(today) => {
+            report.add_note(
+                &repo.name,
+                None,
+                format!("`borg check` skipped by the schedule \"{schedule}\""),
+            );
+            false
+        }
+        _ => repo.run_check,
+    }
+}
+
+/// Create the report of the checks on repository level, which apply to all archive globs
+fn repository_report(
+    repo: &Repository,
+    borg: &Borg,
+    info: Option<&borg::Info>,
+    previous_unique_csize: Option<i64>,
+) -> Report {
+    let mut report = Report::new();
+    if let Some(info) = info {
+        report.append(Report::from_repository_checks(
+            repo,
+            info,
+            previous_unique_csize,
+        ));
+        // Compare the repository size with its storage quota. The fast mode queries only `borg info`.
+        if repo.quota_warn_percent.is_some() && !cli::args().fast {
+            report.append(Report::from_storage_quota(
+                repo,
+                info.cache.stats.unique_csize,
+                borg.storage_quota(),
+            ));
+        }
+    }
+
+    // Check the free space of the filesystem of a local repository
+    if let Some(path) = repo.local_path() {
+        report.append(Report::from_free_space(repo, utils::free_space(&path)));
+    }
+    report
+}
+
+/// Run `borg prune` on the `repo` on request, unless its `report` has errors or warnings
+fn prune_report(repo: &Repository, borg: &Borg, report: &Report) -> Report {
+    if !repo.run_prune {
+        return Report::new();
+    }
+    if report.has_warning_or_error_for(&repo.name) {
+        let mut report = Report::new();
+        report.add_note(
+            &repo.name,
+            None,
+            "`borg prune` was skipped due to errors or warnings",
+        );
+        return report;
+    }
+    // The `--keep-*` rules apply to each archive glob on its own
+    let mut report = Report::new();
+    for archive_glob in repo.prune_globs() {
+        report.append(Report::from_borg_prune_result(
+            repo,
+            archive_glob,
+            &borg.prune(archive_glob, &repo.prune_options),
+        ));
+    }
+    report
+}
+
 /// Create a report for a single `Repository`
-fn create_report(repo: &Repository) -> Report {
+#[allow(clippy::too_many_lines)]
+fn create_report(repo: &Repository, bin: &std::path::Path, store: &state::Store) -> Report {
     let mut report = Report::new();
-    let borg = Borg::from(repo);
+    report.add_repository(repo);
+    for warning in &repo.config_warnings {
+        report.add_warning(&repo.name, None, warning.clone());
+    }
+    // Show the progress of `borg check` e.g. "repo: Checking segments 42.0%"
+    let progress = |message: &str| {
+        let status = format!("{}: {message}", repo.name);
+        emit_progress(&status);
+        notify_status(&status, false);
+    };
+    let borg = Borg::from(repo).with_binary(bin).with_progress(&progress);
+
+    // Load the state of the previous run
+    let mut state = store.load(&repo.name).unwrap_or_else(|e| {
+        report.add_warning(&repo.name, None, format!("{e:#}"));
+        // Start over after a state file, which cannot be parsed. The next save replaces it.
+        store.is_enabled().then(State::default)
+    });
+    let previous_unique_csize = state
+        .as_ref()
+        .and_then(State::last)
+        .map(|run| run.unique_csize);
+    // The last successful `borg info` response for repository wide checks
+    let mut repository_info = None;
+    let run_check = check_due(repo, &mut report);
 
     // Process all archive_globs or process `None` when no filter is given
     let mut archive_globs = repo.archive_globs.clone().into_iter().peekable();
@@ -74,7 +276,7 @@ fn create_report(repo: &Repository) -> Report {
         let archive_glob = archive_glob.as_deref();
 
         // Query `borg info` on the repository
-        let info_result = borg.info(archive_glob);
+        let info_result = borg.info(archive_glob, repo.last_archives);
 
         // If there is a glob, a result but no matching archive then warn about the glob and skip processing.
         if archive_glob.is_some() && info_result.as_ref().is_ok_and(|i| i.archives.is_empty()) {
@@ -87,47 +289,62 @@ fn create_report(repo: &Repository) -> Report {
                 ),
             );
         } else {
+            // Query `borg list` for the oldest archive, when there is a newest one
+            let oldest = match &info_result {
+                Ok(info) if !info.archives.is_empty() && !cli::args().fast => {
+                    match borg.list_first(archive_glob) {
+                        Ok(list) => list.archives.first().map(|a| a.start),
+                        Err(e) => {
+                            report.add_error(&repo.name, archive_glob, e.to_string());
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+
             // Parse the response into the Report
             report.append(Report::from_borg_info_result(
-                &repo.name,
+                repo,
                 archive_glob,
                 &info_result,
+                previous_unique_csize,
+                oldest,
             ));
 
             // Perform sanity checks
             if let Ok(info_result) = &info_result {
+                repository_info = Some(info_result.clone());
                 report.append(Report::from_sanity_checks(
                     &repo.name,
                     archive_glob,
                     info_result,
                     repo.max_age_hours,
+                    repo.allow_empty,
+                    archive_glob
+                        .and_then(|glob| repo.glob_hostnames.get(glob))
+                        .map(String::as_str),
                 ));
-            }
-
-            // Query `borg check` on the archives
-            if repo.run_check {
-                match &info_result {
-                    Ok(info) if !info.archives.is_empty() => {
-                        for archive in &info.archives {
-                            report.append(Report::from_borg_check_result(
-                                &repo.name,
-                                archive_glob,
-                                Some(&archive.name),
-                                &borg.check(Some(&archive.name), &repo.check_options),
-                            ));
-                        }
-                    }
-                    // Check the whole repository, when there are no archives found (and no glob was given initially)
-                    // -> An empty repository can also be checked.
-                    Ok(_) => report.append(Report::from_borg_check_result(
+                if repo.clock_check {
+                    report.append(Report::from_clock_check(
                         &repo.name,
                         archive_glob,
-                        None,
-                        &borg.check(None, &repo.check_options),
-                    )),
-                    Err(_) => {}
+                        info_result,
+                        repo.last_by,
+                    ));
                 }
             }
+
+            // Query `borg check` on the archives
+            if let (true, Ok(info)) = (run_check, &info_result) {
+                report.append(check_report(
+                    repo,
+                    &borg,
+                    archive_glob,
+                    info,
+                    state.as_mut(),
+                ));
+            }
         }
 
         if archive_globs.peek().is_none() {
@@ -135,40 +352,191 @@ fn create_report(repo: &Repository) -> Report {
         }
     }
 
+    // Perform checks on repository level
+    report.append(repository_report(
+        repo,
+        &borg,
+        repository_info.as_ref(),
+        previous_unique_csize,
+    ));
+
+    // Prune the archives of a repository without any issue
+    report.append(prune_report(repo, &borg, &report));
+
+    // Persist the state for the next run (a failed run keeps the previous repository info)
+    if let Some(state) = &mut state {
+        persist_state(repo, &mut report, state, store, repository_info.as_ref());
+    }
+
     report
 }
 
-fn main() -> Result<()> {
-    // Collect the command line options
-    let args = cli::args();
+/// Add the report of each of the `repositories` to the `report`.
+/// When the run exceeds `max_runtime`, the remaining repositories are skipped with a warning.
+fn create_reports(
+    repositories: &[Repository],
+    store: &state::Store,
+    max_runtime: Option<std::time::Duration>,
+    report: &mut Report,
+) {
+    let started = std::time::Instant::now();
+    let mut binaries = borg::Binaries::default();
+    for (processed, repo) in repositories.iter().enumerate() {
+        if let Some(max_runtime) = max_runtime.filter(|d| started.elapsed() >= *d) {
+            // Each skipped repository counts in the status and the mail policy
+            let budget = format::human_duration(
+                jiff::SignedDuration::try_from(max_runtime).unwrap_or_default(),
+            );
+            for skipped in &repositories[processed..] {
+                report.add_repository(skipped);
+                report.add_warning(
+                    &skipped.name,
+                    None,
+                    format!("Skipped, the run was truncated due to the time budget of {budget}"),
+                );
+            }
+            break;
+        }
+        notify_progress(processed, repositories.len());
+        emit_progress(format!("Process repository: {:?}", &repo.name));
+        // A missing borg binary is an error of the repository and does not stop the run
+        let mut repo_report = match binaries.resolve(repo) {
+            Ok(bin) => create_report(repo, &bin, store),
+            Err(e) => {
+                let mut repo_report = Report::new();
+                repo_report.add_repository(repo);
+                repo_report.add_error(&repo.name, None, format!("{e:#}"));
+                repo_report
+            }
+        };
+        repo_report.summary.set_tiers(&repo.tiers);
+        report.append(repo_report);
+        emit_progress("Done."); // This needs to be a short message to get fully overwritten by the next console message.
+    }
+    notify_progress(repositories.len(), repositories.len());
+}
 
-    // Print extended help and early exit?
-    if args.print_help2man {
-        cli::print_help2man()?;
-        std::process::exit(0);
+/// Evaluate the state based checks of the `report` and save the `state` of this run
+fn persist_state(
+    repo: &Repository,
+    report: &mut Report,
+    state: &mut State,
+    store: &state::Store,
+    info: Option<&borg::Info>,
+) {
+    if repo.run_check && report.checks_passed() {
+        state.last_check_success = Some(jiff::Timestamp::now());
     }
+    if !report.checks.is_empty() {
+        let passed = report.checks_passed();
+        match state.last_check_passed {
+            Some(true) if !passed => report.set_check_change(&repo.name, CheckChange::Failing),
+            Some(false) if passed => report.set_check_change(&repo.name, CheckChange::Recovered),
+            _ => {}
+        }
+        state.last_check_passed = Some(passed);
+    }
+    report.append(Report::from_check_interval(
+        &repo.name,
+        state.last_check_success,
+        repo.check_max_interval_hours,
+    ));
+    if let Some(info) = info {
+        let last_modified = info.repository.last_modified;
+        report.append(Report::from_stale_runs(
+            &repo.name,
+            state.unmodified_runs(last_modified),
+            repo.stale_runs,
+            last_modified,
+        ));
+        if repo.rollback_check {
+            report.append(Report::from_rollback_check(
+                &repo.name,
+                state.last().and_then(|run| run.last_modified),
+                last_modified,
+            ));
+        }
+        if let Some(anomaly_sigma) = repo.anomaly_sigma {
+            #[allow(clippy::cast_precision_loss)]
+            let baseline =
+                anomaly::Baseline::new(state.history.iter().map(|run| run.unique_csize as f64));
+            report.append(Report::from_anomaly_check(
+                &repo.name,
+                baseline,
+                info.cache.stats.unique_csize,
+                anomaly_sigma,
+            ));
+        }
+    }
+    let previous = state.last();
+    state.push(state::Run {
+        timestamp: jiff::Timestamp::now(),
+        unique_csize: info.map_or(previous.map_or(0, |run| run.unique_csize), |info| {
+            info.cache.stats.unique_csize
+        }),
+        last_modified: info
+            .map(|info| info.repository.last_modified)
+            .or(previous.and_then(|run| run.last_modified)),
+        errors: report.count_errors(),
+        warnings: report.count_warnings(),
+    });
+    if let Err(e) = store.save(state, &repo.name) {
+        report.add_warning(&repo.name, None, format!("{e:#}"));
+    }
+}
+
+/// All vars of the environment, which are unicode
+fn unicode_env_vars() -> impl Iterator<Item = (String, String)> {
+    std::env::vars_os().filter_map(|(k, v)| k.into_string().ok().zip(v.into_string().ok()))
+}
 
+/// Collect all `Repository` configurations from the *.env files and the environment
+fn collect_repositories(args: &cli::Args) -> Result<Vec<Repository>> {
     // Find all *.env files and parse them into a `Repository` configuration
-    let mut repositories = collect_env_files(&args.env_dirs)?
+    let mut repositories = collect_files(&args.env_dirs, "env")?
         .iter()
         .map(Repository::from_env_file)
         .collect::<Result<Vec<Repository>>>()?;
 
+    // Find all *.key files and pair them with the repository location
+    if let Some(location) = &args.key_repo {
+        for file in collect_files(&args.key_dirs, "key")? {
+            repositories.push(Repository::from_key_file(&file, location)?);
+        }
+    }
+
+    // A single repository can be read from stdin
+    if let Some(repo_name) = &args.env_stdin {
+        let env = dotenvy::from_read_iter(std::io::stdin())
+            .collect::<Result<borg::Env, dotenvy::Error>>()
+            .context("Cannot parse the env file from stdin")?;
+        repositories.push(Repository::from_env(repo_name.clone(), env)?);
+    }
+
+    // Many repositories can be passed as groups of indexed env vars
+    if let Some(prefix) = &args.env_groups {
+        for (repo_name, env) in repository::env_groups(prefix, unicode_env_vars())? {
+            repositories.push(Repository::from_env(repo_name, env)?);
+        }
+    }
+
     // A single repository can be passed directly
     let mut repo_from_env: Option<String> = None;
     if let Some(repo_name) = &args.env_inherit {
         repo_from_env = Some(repo_name.to_string());
     }
-    // If neither --env-dir nor --env-inherit are provided:
+    // If neither --env-dir, --key-dir, --env-stdin, --env-groups nor --env-inherit are provided:
     // Fallback to inherit an unnamed repository using the final path component as repo name.
-    else if args.env_dirs.is_empty() {
-        if let Some(repo_name) = std::env::var_os("BORG_REPO")
-            .map(std::path::PathBuf::from)
-            .as_deref()
-            .and_then(std::path::Path::file_name)
-            .and_then(std::ffi::OsStr::to_str)
+    else if args.env_dirs.is_empty()
+        && args.key_dirs.is_empty()
+        && args.env_stdin.is_none()
+        && args.env_groups.is_none()
+    {
+        if let Some(repo_name) = std::env::var("BORG_REPO")
+            .ok()
+            .and_then(|repo| repository::Location::parse(&repo).name())
         {
-            repo_from_env = Some(repo_name.to_string());
+            repo_from_env = Some(repo_name);
         } else {
             bail!("No value for 'BORG_REPO' was provided. For more information, try '--help'.");
         }
@@ -177,40 +545,184 @@ fn main() -> Result<()> {
     if let Some(repo_name) = repo_from_env {
         repositories.push(Repository::from_env(
             repo_name,
-            std::env::vars_os()
-                // Ignore BORG_* vars, which are not unicode
-                .filter_map(|(k, v)| k.into_string().ok().zip(v.into_string().ok()))
+            unicode_env_vars()
                 .filter(|(k, _)| k.starts_with("BORG_"))
                 .collect(),
         )?);
     }
 
-    // Confirm service startup after parsing all files and directories
-    sd_notify::notify(false, &[sd_notify::NotifyState::Ready])?;
+    ensure!(
+        !(args.require_repos && repositories.is_empty()),
+        "No repository is configured. No *.env files found in {:?}",
+        &args.env_dirs
+    );
 
-    let mut report = Report::new();
-    if repositories.is_empty() {
-        report.add_warning(
-            "",
-            None,
-            format!("No *.env files found in {:?}", &args.env_dirs),
-        );
+    // Report each virtual repository on its own
+    let mut repositories: Vec<Repository> = repositories
+        .into_iter()
+        .flat_map(Repository::into_virtual_repos)
+        .collect();
+
+    // Prompt once for a passphrase shared by all repositories without one
+    if args.ask_passphrase && repositories.iter().any(Repository::lacks_passphrase) {
+        if !std::io::stdin().is_terminal() {
+            bail!("The option '--ask-passphrase' requires an interactive terminal.");
+        }
+        let passphrase = rpassword::prompt_password("Enter passphrase for repositories: ")
+            .context("Cannot read the passphrase from the terminal")?;
+        for repo in repositories.iter_mut().filter(|r| r.lacks_passphrase()) {
+            repo.env
+                .insert("BORG_PASSPHRASE".to_string(), passphrase.clone());
+        }
     }
-    for repo in repositories {
-        emit_progress(format!("Process repository: {:?}", &repo.name));
-        report.append(create_report(&repo));
-        emit_progress("Done."); // This needs to be a short message to get fully overwritten by the next console message.
+
+    Ok(repositories)
+}
+
+/// Parse all repository configurations without running `borg` and print each error
+fn check_config(args: &cli::Args) -> Result<()> {
+    let files = collect_files(&args.env_dirs, "env")?;
+    let mut invalid = 0;
+    if let Err(e) = repository::global_mail_on() {
+        invalid += 1;
+        println!("Error: {e:#}");
     }
+    let mut names = Vec::new();
+    for file in &files {
+        match Repository::from_env_file(file) {
+            Ok(repo) => {
+                println!("OK: {:?} ({file:?})", repo.name);
+                names.push(repo.name);
+            }
+            Err(e) => {
+                invalid += 1;
+                println!("Error: {e:#}");
+            }
+        }
+    }
+    for name in duplicate_names(names.iter().map(String::as_str)) {
+        println!("Warning: The repository name {name:?} is not unique");
+    }
+    ensure!(
+        invalid == 0,
+        "{invalid} invalid configuration(s) found in {} file(s)",
+        files.len()
+    );
+    Ok(())
+}
 
-    // Write report to stdout if not written somewhere else
+/// Run the post `command` via `sh -c` with the written report files and counts in its environment.
+/// A failure is shown on stderr.
+fn run_post_command(args: &cli::Args, report: &Report, command: &str) {
+    let files = [
+        ("BORGREPORT_TEXT_FILE", &args.text_file),
+        ("BORGREPORT_HTML_FILE", &args.html_file),
+        ("BORGREPORT_JSON_FILE", &args.json_file),
+        ("BORGREPORT_METRICS_FILE", &args.metrics_file),
+    ];
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(files.into_iter().filter_map(|(key, file)| {
+            file.as_ref()
+                .filter(|f| f.as_os_str() != "-")
+                .map(|f| (key, f.as_os_str()))
+        }))
+        .env("BORGREPORT_ERRORS", report.count_errors().to_string())
+        .env("BORGREPORT_WARNINGS", report.count_warnings().to_string())
+        .env("BORGREPORT_STATUS", report.status().as_str())
+        .stdin(std::process::Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => eprintln!(
+            "Error: The post command failed ({}):\n{}{}",
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => eprintln!("Error: Cannot run the post command: {e}"),
+    }
+}
+
+/// Renew the metrics file with a report of the error `e`
+fn write_error_metrics(args: &cli::Args, e: &anyhow::Error) -> Result<()> {
+    if let Some(file) = args.metrics_file.as_ref().filter(|f| f.as_os_str() != "-") {
+        let mut report = Report::new();
+        report.add_error("", None, format!("{e:#}"));
+        write_file(file, &render(&report, format::Metrics)?)?;
+    }
+    Ok(())
+}
+
+/// Format the `data` with the `formatter` and redact all matches of the `--redact` patterns
+fn render<T, F>(data: &T, formatter: F) -> Result<String>
+where
+    T: Formattable,
+    F: format::Formatter<T>,
+{
+    redact(data.to_string(formatter)?)
+}
+
+/// Mask the matches of the `--redact` patterns in the `output`
+fn redact(mut output: String) -> Result<String> {
+    for pattern in redact_patterns()? {
+        output = pattern.replace_all(&output, "***").into_owned();
+    }
+    Ok(output)
+}
+
+/// The compiled `--redact` patterns
+fn redact_patterns() -> Result<&'static [regex::Regex]> {
+    static PATTERNS: std::sync::OnceLock<Vec<regex::Regex>> = std::sync::OnceLock::new();
+    if let Some(patterns) = PATTERNS.get() {
+        return Ok(patterns);
+    }
+    let patterns = cli::args()
+        .redact
+        .iter()
+        .flat_map(|patterns| patterns.split(','))
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(regex::Regex::new)
+        .collect::<Result<Vec<_>, _>>()
+        .context(format!("Cannot parse parameter {}", cli::args::REDACT))?;
+    Ok(PATTERNS.get_or_init(|| patterns))
+}
+
+/// Append a timestamped summary line of the `report` to the log `file`.
+/// The line is written at once to not interleave with a concurrent run.
+fn append_log(file: &std::path::Path, report: &Report) -> Result<()> {
+    use std::io::Write;
+
+    let line = format!(
+        "{} status={} repositories={} errors={} warnings={} size={}\n",
+        jiff::Timestamp::now().round(jiff::Unit::Second)?,
+        report.status().as_str(),
+        report.repositories.len(),
+        report.count_errors(),
+        report.count_warnings(),
+        human_repr::HumanCount::human_count_bytes(report.total_unique_csize())
+    );
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)
+        .and_then(|mut f| f.write_all(line.as_bytes()))
+        .with_context(|| format!("Cannot append to the log file {file:?}"))
+}
+
+/// Write the `report` into the requested files and formats.
+/// Returns True if any output was produced.
+fn write_outputs(args: &cli::Args, report: &Report) -> Result<bool> {
     let mut output_processed = false;
 
     // Write text file ?
     if let Some(file) = &args.text_file {
         if file.to_string_lossy().eq("-") {
-            print!("{}", report.to_string(format::Text)?);
+            print!("{}", render(report, format::Text)?);
         } else {
-            std::fs::write(file, report.to_string(format::Text)?)?;
+            write_file(file, &render(report, format::Text)?)?;
         }
         output_processed = true;
     }
@@ -218,9 +730,19 @@ fn main() -> Result<()> {
     // Write html file ?
     if let Some(file) = &args.html_file {
         if file.to_string_lossy().eq("-") {
-            print!("{}", report.to_string(format::Html)?);
+            print!("{}", render(report, format::Html)?);
+        } else {
+            write_file(file, &render(report, format::Html)?)?;
+        }
+        output_processed = true;
+    }
+
+    // Write json file ?
+    if let Some(file) = &args.json_file {
+        if file.to_string_lossy().eq("-") {
+            print!("{}", render(report, format::Json)?);
         } else {
-            std::fs::write(file, report.to_string(format::Html)?)?;
+            write_file(file, &render(report, format::Json)?)?;
         }
         output_processed = true;
     }
@@ -228,15 +750,198 @@ fn main() -> Result<()> {
     // Write metrics file ?
     if let Some(file) = &args.metrics_file {
         if file.to_string_lossy().eq("-") {
-            print!("{}", report.to_string(format::Metrics)?);
+            print!("{}", render(report, format::Metrics)?);
+        } else {
+            write_file(file, &render(report, format::Metrics)?)?;
+        }
+        output_processed = true;
+    }
+
+    // Append a summary line to the log file ? (not an output of the report)
+    if let Some(file) = &args.log_file {
+        append_log(file, report)?;
+    }
+
+    // Write a single summary line to stdout ?
+    if args.oneline {
+        print!("{}", render(report, format::Oneline)?);
+        output_processed = true;
+    }
+
+    // Write multiple formats to stdout ?
+    if !args.formats.is_empty() {
+        write_formats(report, &args.formats)?;
+        output_processed = true;
+    }
+
+    Ok(output_processed)
+}
+
+/// Write the `formats` of the `report` to stdout, each preceded by a delimiter line
+fn write_formats(report: &Report, formats: &[cli::OutputFormat]) -> Result<()> {
+    for format in formats {
+        let (name, output) = match format {
+            cli::OutputFormat::Text => ("text", render(report, format::Text)?),
+            cli::OutputFormat::Html => ("html", render(report, format::Html)?),
+            cli::OutputFormat::Metrics => ("metrics", render(report, format::Metrics)?),
+            cli::OutputFormat::Json => ("json", render(report, format::Json)?),
+        };
+        println!("==> {name} <==");
+        print!("{output}");
+    }
+    Ok(())
+}
+
+/// Send a sample report to `--mail-to` without running `borg`
+fn test_mail(args: &cli::Args) -> Result<()> {
+    let mail_to = args
+        .mail_to
+        .as_ref()
+        .context(format!("Parameter {} is required", cli::args::MAILTOADDR))?;
+    let mut report = Report::new();
+    report.add_warning(
+        "sample",
+        None,
+        "This is a test mail of borgreport. No repository was queried.",
+    );
+    send_mail(
+        mail_to,
+        args.mail_from.as_ref(),
+        &format!(
+            "{}Backup report test ({})",
+            format::environment_prefix(),
+            jiff::Zoned::now().date()
+        ),
+        render(&report, format::Text)?,
+        render(&report, format::Html)?,
+    )?;
+    eprintln!("A test mail was sent to {mail_to}");
+    Ok(())
+}
+
+/// Print the resolved options and the `borg` environment of the repository `name` with masked secrets
+fn print_env(args: &cli::Args, name: &str) -> Result<()> {
+    use std::fmt::Write;
+    let repo = collect_repositories(args)?
+        .into_iter()
+        .find(|r| r.name == name)
+        .with_context(|| format!("Repository {name:?} not found"))?;
+    let environment = Borg::from(&repo).environment();
+
+    let mut output = String::from("Options:\n");
+    for (key, value) in repo.options() {
+        writeln!(output, "  {key}={value}")?;
+    }
+    output.push_str("Environment:\n");
+    for (key, value) in &environment {
+        let key = key.to_string_lossy();
+        if ["BORG_PASSPHRASE", "BORG_PASSCOMMAND", "BORG_NEW_PASSPHRASE"].contains(&key.as_ref()) {
+            writeln!(output, "  {key}=***")?;
         } else {
-            std::fs::write(file, report.to_string(format::Metrics)?)?;
+            writeln!(output, "  {key}={}", value.to_string_lossy())?;
+        }
+    }
+    print!("{}", redact(output)?);
+    Ok(())
+}
+
+/// Summarize the state history of all repositories over the `period` and write it to the text
+/// and html outputs or send it by mail
+fn digest(args: &cli::Args, period: cli::Digest) -> Result<()> {
+    redact_patterns()?;
+    let state_dir = args
+        .state_dir
+        .as_deref()
+        .context(format!("Parameter {} is required", cli::args::STATE_DIR))?;
+
+    let mut digest = digest::Digest::new(period)?;
+    for repo in collect_repositories(args)? {
+        digest.add(&repo.name, &State::load(state_dir, &repo.name)?);
+    }
+
+    let mut output_processed = false;
+    for (file, text) in [
+        (&args.text_file, render(&digest, format::Text)?),
+        (&args.html_file, render(&digest, format::Html)?),
+    ] {
+        match file {
+            Some(file) if file.as_os_str() == "-" => print!("{text}"),
+            Some(file) => write_file(file, &text)?,
+            None => continue,
         }
         output_processed = true;
     }
 
-    // Send report per mail ?
     if let Some(mail_to) = &args.mail_to {
+        send_mail(
+            mail_to,
+            args.mail_from.as_ref(),
+            &format!(
+                "Backup digest ({}) {}",
+                period.as_str(),
+                jiff::Zoned::now().date()
+            ),
+            render(&digest, format::Text)?,
+            render(&digest, format::Html)?,
+        )?;
+        output_processed = true;
+    }
+
+    if !output_processed {
+        print!("{}", render(&digest, format::Text)?);
+    }
+    Ok(())
+}
+
+/// Create the report for all repositories and write it to all outputs
+fn run(args: &cli::Args) -> Result<Report> {
+    // Fail early on invalid patterns instead of on the first output
+    redact_patterns()?;
+
+    // Collect all `Repository` configurations
+    // On failure the metrics file is still renewed to show that borgreport ran.
+    let repositories = match collect_repositories(args) {
+        Ok(repositories) => repositories,
+        Err(e) => {
+            write_error_metrics(args, &e)?;
+            return Err(e);
+        }
+    };
+
+    // Confirm service startup after parsing all files and directories
+    sd_notify::notify(false, &[sd_notify::NotifyState::Ready])?;
+
+    let mut report = Report::new();
+    if repositories.is_empty() {
+        report.add_warning(
+            "",
+            None,
+            format!("No *.env files found in {:?}", &args.env_dirs),
+        );
+    }
+    for name in duplicate_names(repositories.iter().map(|r| r.name.as_str())) {
+        report.add_warning(
+            name,
+            None,
+            "The repository name is not unique. Rename an env file to tell the repositories apart.",
+        );
+    }
+    // The state of the previous run (skipped in fast mode) is best-effort
+    let store = state::Store::new(args.state_dir.as_deref().filter(|_| !args.fast));
+    create_reports(&repositories, &store, args.max_runtime, &mut report);
+
+    // Write report to stdout if not written somewhere else
+    let mut output_processed = write_outputs(args, &report)?;
+
+    // Send report per mail ? (if any repository requires it by its mail policy)
+    // Messages not related to a repository are evaluated with the global mail policy.
+    let global_mail_on = repository::global_mail_on()?;
+    let mail_required = repositories
+        .iter()
+        .any(|repo| report.requires_mail_for(&repo.name, repo.mail_on))
+        || ((report.has_errors_for("") || report.has_warnings_for(""))
+            && report.requires_mail_for("", global_mail_on));
+    if let Some(mail_to) = args.mail_to.as_ref().filter(|_| mail_required) {
         let mut suffix = vec![];
         if report.has_errors() {
             suffix.push(format!("Errors:{}", report.count_errors()));
@@ -248,23 +953,152 @@ fn main() -> Result<()> {
             mail_to,
             args.mail_from.as_ref(),
             &format!(
-                "Backup report ({}) {}",
+                "{}Backup report ({}) {}",
+                format::environment_prefix(),
                 jiff::Zoned::now().date(),
                 suffix.join(" ")
             ),
-            report.to_string(format::Text)?,
-            report.to_string(format::Html)?,
+            render(&report, format::Text)?,
+            render(&report, format::Html)?,
         )?;
+    }
+    if args.mail_to.is_some() {
         output_processed = true;
     }
 
     // Print to stdout
     if !output_processed {
-        print!("{}", report.to_string(format::Text)?);
+        print!("{}", render(&report, format::Text)?);
+    };
+
+    // Run the post command after all outputs are produced
+    if let Some(command) = &args.post_command {
+        run_post_command(args, &report, command);
+    }
+
+    Ok(report)
+}
+
+/// Repeat the run every `interval` until SIGTERM or SIGINT is received.
+/// An error does not stop the loop but is printed to stderr.
+fn watch(args: &cli::Args, interval: std::time::Duration) -> Result<()> {
+    let terminate = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+        signal_hook::flag::register(signal, Arc::clone(&terminate))?;
+    }
+
+    // Serve the report of the latest run
+    let latest = Arc::new(Mutex::new(None));
+    if let Some(addr) = &args.serve {
+        utils::serve(addr, Arc::clone(&latest))?;
+    }
+
+    while !terminate.load(Ordering::Relaxed) {
+        let next_run = std::time::Instant::now() + interval;
+        // An error of a single run must not end the watch loop
+        let published = run(args).and_then(|report| {
+            if args.serve.is_none() {
+                return Ok(None);
+            }
+            Ok(Some(utils::Published {
+                html: render(&report, format::Html)?,
+                metrics: render(&report, format::Metrics)?,
+            }))
+        });
+        match published {
+            Ok(Some(published)) => {
+                if let Ok(mut latest) = latest.lock() {
+                    *latest = Some(published);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Error: {e:#}"),
+        }
+
+        let _ = sd_notify::notify(
+            false,
+            &[sd_notify::NotifyState::Status(&format!(
+                "Waiting {}s for the next run",
+                interval.as_secs()
+            ))],
+        );
+        // Sleep in short steps to react on a signal
+        while !terminate.load(Ordering::Relaxed) {
+            let remaining = next_run.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            std::thread::sleep(remaining.min(std::time::Duration::from_secs(1)));
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<ExitCode> {
+    // Collect the command line options
+    let args = cli::args();
+
+    // Print extended help and early exit?
+    if args.print_help2man {
+        cli::print_help2man()?;
+        std::process::exit(0);
+    }
+
+    // Print the JSON Schema of the report and early exit?
+    if args.print_json_schema {
+        format::json::print_schema()?;
+        std::process::exit(0);
+    }
+
+    // Validate the configuration and early exit?
+    if args.config_check {
+        return check_config(args).map(|()| ExitCode::SUCCESS);
+    }
+
+    // Print the environment of a repository and early exit?
+    if let Some(name) = &args.print_env {
+        return print_env(args, name).map(|()| ExitCode::SUCCESS);
+    }
+
+    // Send a sample report and early exit?
+    if args.test_mail {
+        return test_mail(args).map(|()| ExitCode::SUCCESS);
+    }
+
+    // Summarize the state history and early exit?
+    if let Some(period) = args.digest {
+        return digest(args, period).map(|()| ExitCode::SUCCESS);
+    }
+
+    // Run once or repeatedly
+    let status = if let Some(interval) = args.watch {
+        // The aggregated status of a single run is not meaningful, `--exit-status` conflicts with `--watch`
+        watch(args, interval)?;
+        report::Status::Ok
+    } else {
+        run(args)?.status()
     };
 
     // Announce service shutdown, if we are a systemd service
     sd_notify::notify(false, &[sd_notify::NotifyState::Stopping])?;
 
-    Ok(())
+    // Exit with the aggregated status?
+    if args.exit_status {
+        Ok(ExitCode::from(status.exit_code()))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+mod tests {
+    #[test]
+    fn duplicate_names() {
+        use super::duplicate_names;
+
+        assert!(duplicate_names(["prod", "test"]).is_empty());
+        assert_eq!(
+            duplicate_names(["prod", "test", "prod", "prod", "test", "dev"]),
+            ["prod", "test"]
+        );
+    }
 }
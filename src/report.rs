@@ -1,12 +1,20 @@
 // SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::ops::Deref;
+use std::{collections::BTreeMap, ops::Deref};
 
 use anyhow::Result;
+use human_repr::HumanCount;
+use schemars::JsonSchema;
+use serde::Serialize;
 
-use crate::borg;
 pub(crate) use crate::format::Formattable;
+use crate::{
+    anomaly::Baseline,
+    borg,
+    cli::{self, MailOn, Severity},
+    repository::Repository,
+};
 
 /// Helper to associate data types used in the report
 pub(crate) trait Component {}
@@ -14,6 +22,38 @@ impl Component for Report {}
 impl Component for Section<BulletPoint> {}
 impl Component for Section<SummaryEntry> {}
 impl Component for Section<ChecksEntry> {}
+impl Component for Section<PruneEntry> {}
+impl Component for HostRollup {}
+
+/// The aggregated status of all repositories in a report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Status {
+    Ok,
+    Degraded,
+    Critical,
+}
+impl Status {
+    /// All states in order of severity
+    pub(crate) const ALL: [Self; 3] = [Self::Ok, Self::Degraded, Self::Critical];
+
+    /// The state name used in the metrics
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Degraded => "degraded",
+            Self::Critical => "critical",
+        }
+    }
+
+    /// The exit code following the nagios plugin convention
+    pub(crate) fn exit_code(self) -> u8 {
+        match self {
+            Self::Ok => 0,
+            Self::Degraded => 1,
+            Self::Critical => 2,
+        }
+    }
+}
 
 /// A report contains sections with structured data
 pub(crate) struct Report {
@@ -21,10 +61,30 @@ pub(crate) struct Report {
     pub(crate) errors: Section<BulletPoint>,
     /// The warning section shows borg messages and additional sanity checks
     pub(crate) warnings: Section<BulletPoint>,
+    /// The note section shows informational messages, which do not affect the status
+    pub(crate) notes: Section<BulletPoint>,
     /// The summary section shows statistics for the recent backup archives
     pub(crate) summary: Section<SummaryEntry>,
     /// The check section shows results from `borg check`
     pub(crate) checks: Section<ChecksEntry>,
+    /// The prune section shows results from `borg prune`
+    pub(crate) prunes: Section<PruneEntry>,
+    /// Friendly names of the archive globs per repository used as metric labels
+    pub(crate) glob_labels: BTreeMap<(String, String), String>,
+    /// All processed repositories and whether they are critical
+    pub(crate) repositories: BTreeMap<String, bool>,
+    /// The physical repository of each virtual repository `<REPO>/<NAME>`
+    pub(crate) parents: BTreeMap<String, String>,
+    /// Maximum severity per repository in the aggregated status and the mail policy
+    pub(crate) severities: BTreeMap<String, Severity>,
+    /// Time of the last successful `borg check` per repository (from the state)
+    pub(crate) last_check_success: BTreeMap<String, jiff::Timestamp>,
+    /// The archive glob per repository, which is displayed in the summary
+    pub(crate) primary_globs: BTreeMap<String, String>,
+    /// Available bytes on the filesystem of each local repository
+    pub(crate) free_space: BTreeMap<String, u64>,
+    /// Storage quota and its used bytes of each repository with a quota
+    pub(crate) quotas: BTreeMap<String, (u64, i64)>,
 }
 impl Report {
     /// Create a new empty `Report`
@@ -32,8 +92,18 @@ impl Report {
         Self {
             errors: Section::new(),
             warnings: Section::new(),
+            notes: Section::new(),
             summary: Section::new(),
             checks: Section::new(),
+            prunes: Section::new(),
+            glob_labels: BTreeMap::new(),
+            repositories: BTreeMap::new(),
+            parents: BTreeMap::new(),
+            severities: BTreeMap::new(),
+            last_check_success: BTreeMap::new(),
+            primary_globs: BTreeMap::new(),
+            free_space: BTreeMap::new(),
+            quotas: BTreeMap::new(),
         }
     }
 
@@ -42,13 +112,130 @@ impl Report {
         let Self {
             errors,
             warnings,
+            notes,
             summary,
             checks,
+            prunes,
+            glob_labels,
+            repositories,
+            parents,
+            severities,
+            last_check_success,
+            primary_globs,
+            free_space,
+            quotas,
         } = other;
         self.errors.append(errors.into_inner());
         self.warnings.append(warnings.into_inner());
+        self.notes.append(notes.into_inner());
         self.summary.append(summary.into_inner());
         self.checks.append(checks.into_inner());
+        self.prunes.append(prunes.into_inner());
+        self.glob_labels.extend(glob_labels);
+        self.repositories.extend(repositories);
+        self.parents.extend(parents);
+        self.severities.extend(severities);
+        self.last_check_success.extend(last_check_success);
+        self.primary_globs.extend(primary_globs);
+        self.free_space.extend(free_space);
+        self.quotas.extend(quotas);
+    }
+
+    /// Add the `repo` with its critical flag and severity, the friendly names of its archive globs and its primary glob
+    pub fn add_repository(&mut self, repo: &Repository) {
+        self.repositories.insert(repo.name.clone(), repo.critical);
+        self.severities.insert(repo.name.clone(), repo.severity);
+        self.glob_labels.extend(
+            repo.glob_labels
+                .iter()
+                .map(|(glob, label)| ((repo.name.clone(), glob.clone()), label.clone())),
+        );
+        if let Some(glob) = &repo.primary_glob {
+            self.primary_globs.insert(repo.name.clone(), glob.clone());
+        }
+        if let Some(parent) = &repo.parent {
+            self.parents.insert(repo.name.clone(), parent.clone());
+        }
+    }
+
+    /// The total original size and the deduplicated compressed size per physical repository.
+    /// The sizes are the same for all archive globs and all virtual repositories of a repository.
+    fn physical_sizes(&self) -> BTreeMap<&str, (i64, i64)> {
+        let mut repositories = BTreeMap::new();
+        for e in self.summary.iter() {
+            let physical = self.parents.get(&e.repository).unwrap_or(&e.repository);
+            repositories
+                .entry(physical.as_str())
+                .or_insert((e.total_size, e.unique_csize));
+        }
+        repositories
+    }
+
+    /// The total deduplicated compressed size of all repositories
+    pub fn total_unique_csize(&self) -> u64 {
+        let size: i64 = self.physical_sizes().values().map(|(_, u)| u).sum();
+        size.max(0).unsigned_abs()
+    }
+
+    /// The total original size and the stored size of all repositories and the share saved
+    pub fn savings(&self) -> Option<(i64, i64, f64)> {
+        let (total_size, unique_csize) = self
+            .physical_sizes()
+            .values()
+            .fold((0, 0), |(t, u), (total, unique)| (t + total, u + unique));
+        savings_ratio(total_size, unique_csize).map(|ratio| (total_size, unique_csize, ratio))
+    }
+
+    /// Mark all checks of the `repository` with the `change` since the previous check
+    pub fn set_check_change(&mut self, repository: &str, change: CheckChange) {
+        for check in &mut self.checks.0 {
+            if check.repository == repository {
+                check.inner.change = Some(change);
+            }
+        }
+    }
+
+    /// The summary rows to display. A repository with a primary archive glob shows only its rows.
+    pub fn displayed_summary(&self) -> Section<SummaryEntry> {
+        Section(
+            self.summary
+                .iter()
+                .filter(|e| {
+                    self.primary_globs
+                        .get(&e.repository)
+                        .map_or(true, |glob| e.archive_glob.as_ref() == Some(glob))
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// The friendly name of the `archive_glob` in the `repository` or the raw glob otherwise
+    pub fn archive_glob_label(
+        &self,
+        repository: &str,
+        archive_glob: Option<&str>,
+    ) -> Option<String> {
+        archive_glob.map(|glob| {
+            self.glob_labels
+                .get(&(repository.to_string(), glob.to_string()))
+                .map_or(glob, String::as_str)
+                .to_string()
+        })
+    }
+
+    /// Add an informational note to the report, which does not affect the status
+    pub fn add_note(
+        &mut self,
+        repository: &str,
+        archive_glob: Option<&str>,
+        msg: impl Into<String>,
+    ) {
+        self.notes.add_str(
+            repository,
+            archive_glob,
+            add_msg_prefix(repository, archive_glob, msg),
+        );
     }
 
     /// Add a warning message to the report
@@ -99,18 +286,153 @@ impl Report {
         self.warnings.inner().len()
     }
 
+    /// The named tier of the `archive_glob` in the `repository` (as found in the summary)
+    pub fn archive_tier(&self, repository: &str, archive_glob: Option<&str>) -> Option<String> {
+        self.summary
+            .iter()
+            .find(|e| e.repository == repository && e.archive_glob.as_deref() == archive_glob)
+            .and_then(|e| e.tier.clone())
+    }
+
+    /// Returns True if there is an error for the `repository`
+    pub fn has_errors_for(&self, repository: &str) -> bool {
+        self.errors.iter().any(|e| e.repository == repository)
+    }
+
+    /// Returns True if there is a warning for the `repository`
+    pub fn has_warnings_for(&self, repository: &str) -> bool {
+        self.warnings.iter().any(|e| e.repository == repository)
+    }
+
+    /// Returns True if `borg check` ran and all checks passed without any error in the report
+    pub fn checks_passed(&self) -> bool {
+        !self.checks.is_empty()
+            && self.checks.iter().all(|c| c.status.success())
+            && !self.has_errors()
+    }
+
+    /// Returns True if there is a warning or an error for the `repository`
+    pub fn has_warning_or_error_for(&self, repository: &str) -> bool {
+        self.has_errors_for(repository) || self.has_warnings_for(repository)
+    }
+
+    /// The maximum severity of the `repository` (an error for messages not related to a repository)
+    pub fn severity_for(&self, repository: &str) -> Severity {
+        self.severities.get(repository).copied().unwrap_or_default()
+    }
+
+    /// Returns True if the `repository` failed: It has an error or a warning with `--fail-on-warning`.
+    /// An error counts as warning with the severity `warning` and nothing counts with `ignore`.
+    /// The policy affects the aggregated status and the mail policy but not the report itself.
+    pub fn has_failure_for(&self, repository: &str) -> bool {
+        match self.severity_for(repository) {
+            Severity::Ignore => false,
+            Severity::Warning => {
+                cli::args().fail_on_warning && self.has_warning_or_error_for(repository)
+            }
+            Severity::Error => {
+                self.has_errors_for(repository)
+                    || (cli::args().fail_on_warning && self.has_warnings_for(repository))
+            }
+        }
+    }
+
+    /// Returns the names of all healthy repositories in order of appearance.
+    /// A repository is healthy with a last archive, all checks passed and without any warning or error.
+    pub fn healthy_repositories(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for entry in self.summary.iter() {
+            let name = entry.repository.as_str();
+            if !names.contains(&name)
+                && !entry.archive.is_empty()
+                && !self.has_warning_or_error_for(name)
+                && self
+                    .checks
+                    .iter()
+                    .all(|c| c.repository != name || c.status.success())
+            {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// The aggregated status of all repositories by their errors:
+    /// `Ok` without errors, `Critical` when all or a critical repository have errors and `Degraded` otherwise.
+    /// A report with errors but without any repository (e.g. nothing could be parsed) is `Critical`.
+    /// Repositories with the severity `ignore` are not counted.
+    pub fn status(&self) -> Status {
+        if self.repositories.is_empty()
+            && (self.has_errors() || (cli::args().fail_on_warning && self.has_warnings()))
+        {
+            return Status::Critical;
+        }
+        let counted = self
+            .repositories
+            .iter()
+            .filter(|(name, _)| self.severity_for(name) > Severity::Ignore)
+            .count();
+        let failed: Vec<bool> = self
+            .repositories
+            .iter()
+            .filter(|(name, _)| self.has_failure_for(name))
+            .map(|(_, critical)| *critical)
+            .collect();
+        if failed.is_empty() {
+            Status::Ok
+        } else if failed.len() == counted || failed.contains(&true) {
+            Status::Critical
+        } else {
+            Status::Degraded
+        }
+    }
+
+    /// Returns True if the `repository` requires to send the report according to its mail policy `mail_on`
+    pub fn requires_mail_for(&self, repository: &str, mail_on: MailOn) -> bool {
+        match mail_on {
+            MailOn::Always => true,
+            MailOn::Warning => {
+                self.severity_for(repository) > Severity::Ignore
+                    && self.has_warning_or_error_for(repository)
+            }
+            MailOn::Error => self.has_failure_for(repository),
+        }
+    }
+
+    /// Add the failure `e` of a borg command as error with all borg log messages.
+    /// A locked repository is expected to be released again and reported as warning.
+    /// An unreachable repository is reported as warning, when the repository tolerates it.
+    fn add_failure(&mut self, repo: &Repository, archive_glob: Option<&str>, e: &anyhow::Error) {
+        if e.downcast_ref::<borg::Locked>().is_some()
+            || (repo.timeout_as_warning && e.downcast_ref::<borg::Unreachable>().is_some())
+        {
+            self.add_warning(&repo.name, archive_glob, e.to_string());
+        } else {
+            self.add_error(&repo.name, archive_glob, e.to_string());
+        }
+    }
+
     /// Convert a `borg info` result into a report
+    /// The `previous_unique_csize` is the repository size of the previous run (if known).
+    /// The `oldest` is the start time of the oldest archive (if known).
     pub fn from_borg_info_result(
-        repo_name: &str,
+        repo: &Repository,
         archive_glob: Option<&str>,
         info_result: &Result<borg::Info>,
+        previous_unique_csize: Option<i64>,
+        oldest: Option<jiff::civil::DateTime>,
     ) -> Self {
+        let repo_name = repo.name.as_str();
         let mut report = Self::new();
         match &info_result {
             Ok(info) => {
-                report
-                    .summary
-                    .add_from_borg_info(repo_name, archive_glob, info);
+                report.summary.add_from_borg_info(
+                    repo_name,
+                    archive_glob,
+                    info,
+                    previous_unique_csize,
+                    oldest,
+                );
             }
             Err(e) => {
                 // Create an empty summary entry for the repository
@@ -121,20 +443,27 @@ impl Report {
                         ..Default::default()
                     },
                 }]);
-                // Add all borg log messages to the error section
-                report.add_error(repo_name, archive_glob, e.to_string());
+                report.add_failure(repo, archive_glob, e);
             }
         }
         report
     }
 
-    /// Convert a `borg check` result into a report
+    /// Convert a `borg check` result into a report.
+    /// Output lines matching any of the repository's check ignore patterns are dropped.
     pub fn from_borg_check_result(
-        repo_name: &str,
+        repo: &Repository,
         archive_glob: Option<&str>,
         archive_name: Option<&str>,
         check_result: &Result<borg::Check>,
     ) -> Self {
+        let repo_name = repo.name.as_str();
+        let relevant = |text: &str| {
+            text.lines()
+                .filter(|line| !repo.check_ignore_patterns.iter().any(|p| p.is_match(line)))
+                .collect::<Vec<&str>>()
+                .join("\n")
+        };
         let mut report = Self::new();
         match check_result {
             Ok(check) => {
@@ -146,18 +475,370 @@ impl Report {
                         archive_name: archive_name.map(ToString::to_string),
                         duration: check.duration,
                         status: check.status,
+                        timestamp: jiff::Timestamp::now(),
+                        change: None,
+                        peak_rss: check.peak_rss,
+                    },
+                ));
+                let stdout = relevant(&check.stdout);
+                if !stdout.is_empty() {
+                    report.add_warning(repo_name, archive_glob, stdout);
+                }
+                // Route structured log messages by their severity
+                for message in check
+                    .messages
+                    .iter()
+                    .filter(|m| !relevant(&m.message).is_empty())
+                {
+                    match message.levelname {
+                        borg::Level::Warning => {
+                            report.add_warning(repo_name, archive_glob, &message.message);
+                        }
+                        borg::Level::Error | borg::Level::Critical => {
+                            report.add_error(repo_name, archive_glob, &message.message);
+                        }
+                        borg::Level::Debug | borg::Level::Info => {}
+                    }
+                }
+                let stderr = relevant(&check.stderr);
+                if !stderr.is_empty() {
+                    report.add_error(repo_name, archive_glob, stderr);
+                }
+                if let Some(termination) = check.termination() {
+                    report.add_error(repo_name, archive_glob, termination);
+                }
+                // A connection failure is tolerated on request
+                if repo.timeout_as_warning && check.is_unreachable() {
+                    report.warnings.append(std::mem::take(&mut report.errors).0);
+                }
+            }
+            Err(e) => report.add_failure(repo, archive_glob, e),
+        }
+        report
+    }
+
+    /// Parse the response of `borg prune` into the report.
+    /// The errors of borg are reported, when the command failed.
+    pub fn from_borg_prune_result(
+        repo: &Repository,
+        archive_glob: Option<&str>,
+        prune_result: &Result<borg::Prune>,
+    ) -> Self {
+        let repo_name = repo.name.as_str();
+        let mut report = Self::new();
+        match prune_result {
+            Ok(prune) => {
+                let stats = prune.prune_stats();
+                report.prunes.add((
+                    repo_name,
+                    archive_glob,
+                    PruneEntry {
+                        repository: repo_name.to_string(),
+                        duration: prune.duration,
+                        status: prune.status,
+                        pruned: stats.pruned,
+                        kept: stats.kept,
+                        deleted_size: stats.deleted_size,
                     },
                 ));
-                if !check.stdout.is_empty() {
-                    report.add_warning(repo_name, archive_glob, &check.stdout);
+                for message in prune
+                    .messages
+                    .iter()
+                    .filter(|m| m.levelname == borg::Level::Warning)
+                {
+                    report.add_warning(repo_name, archive_glob, &message.message);
                 }
-                if !check.stderr.is_empty() {
-                    report.add_error(repo_name, archive_glob, &check.stderr);
+                // The listing and the stats are logged to stderr: Only a failure is an error
+                if !prune.status.success() {
+                    report.add_error(repo_name, archive_glob, prune.errors());
                 }
             }
+            Err(e) => report.add_failure(repo, archive_glob, e),
+        }
+        report
+    }
+
+    /// Perform checks on repository level, which apply to all archive globs
+    /// - Warn about a repository without encryption
+    /// - Warn about a growth above `max_growth_percent` compared to the `previous_unique_csize`
+    pub fn from_repository_checks(
+        repo: &Repository,
+        info: &borg::Info,
+        previous_unique_csize: Option<i64>,
+    ) -> Self {
+        let mut report = Self::new();
+
+        // warn if the repository is not encrypted
+        if !info.encryption.is_encrypted() {
+            report.add_warning(
+                &repo.name,
+                None,
+                format!(
+                    "Repository is not encrypted (encryption mode: {})",
+                    info.encryption.mode
+                ),
+            );
+        }
+
+        // warn if the repository grew too much since the previous run
+        // A growth from an empty repository has no meaningful percentage
+        let unique_csize = info.cache.stats.unique_csize;
+        if let (Some(previous), Some(max_growth_percent)) = (
+            previous_unique_csize.filter(|p| *p > 0),
+            repo.max_growth_percent,
+        ) {
+            #[allow(clippy::cast_precision_loss)]
+            let growth_percent = (unique_csize - previous) as f64 / previous as f64 * 100.0;
+            if growth_percent > max_growth_percent {
+                report.add_warning(
+                    &repo.name,
+                    None,
+                    format!(
+                        "Repository grew by {growth_percent:.1}% ({}) since the previous run, which exceeds the threshold of {max_growth_percent}%",
+                        (unique_csize - previous).human_count_bytes()
+                    ),
+                );
+            }
+        }
+        report
+    }
+
+    /// Warn when the `unique_csize` deviates by more than `anomaly_sigma` standard deviations from the `baseline`
+    pub fn from_anomaly_check(
+        repo_name: &str,
+        baseline: Option<Baseline>,
+        unique_csize: i64,
+        anomaly_sigma: f64,
+    ) -> Self {
+        let mut report = Self::new();
+        let Some(baseline) = baseline else {
+            return report;
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let sigma = baseline.sigma(unique_csize as f64);
+        if sigma.abs() > anomaly_sigma {
+            report.add_warning(
+                repo_name,
+                None,
+                format!(
+                    "Repository size {} deviates by {sigma:+.1}σ from its moving average of {} (σ = {}), which exceeds the threshold of {anomaly_sigma}σ",
+                    unique_csize.human_count_bytes(),
+                    baseline.mean.human_count_bytes(),
+                    baseline.stddev.human_count_bytes(),
+                ),
+            );
+        }
+        report
+    }
+
+    /// Record the storage `quota` of the repository and its usage by the `unique_csize`.
+    /// Warn when the usage exceeds `quota_warn_percent` of the `repo`. A quota of 0 is no quota.
+    pub fn from_storage_quota(repo: &Repository, unique_csize: i64, quota: Result<u64>) -> Self {
+        let mut report = Self::new();
+        let quota = match quota {
+            Ok(0) => return report,
+            Ok(quota) => quota,
+            Err(e) => {
+                report.add_warning(
+                    &repo.name,
+                    None,
+                    format!("Cannot query the storage quota: {e}"),
+                );
+                return report;
+            }
+        };
+        report
+            .quotas
+            .insert(repo.name.clone(), (quota, unique_csize));
+        #[allow(clippy::cast_precision_loss)]
+        let used_percent = unique_csize as f64 / quota as f64 * 100.0;
+        if let Some(quota_warn_percent) = repo.quota_warn_percent {
+            if used_percent > quota_warn_percent {
+                report.add_warning(
+                    &repo.name,
+                    None,
+                    format!(
+                        "Repository uses {used_percent:.1}% ({}) of its storage quota of {}, which exceeds the threshold of {quota_warn_percent}%",
+                        unique_csize.human_count_bytes(),
+                        quota.human_count_bytes()
+                    ),
+                );
+            }
+        }
+        report
+    }
+
+    /// Record the `free_space` (available and total bytes) of the filesystem of a local repository.
+    /// Warn when it falls below `min_free_bytes` or `min_free_percent` of the `repo`.
+    pub fn from_free_space(repo: &Repository, free_space: std::io::Result<(u64, u64)>) -> Self {
+        let mut report = Self::new();
+        let (available, total) = match free_space {
+            Ok(free_space) => free_space,
             Err(e) => {
-                // Add all borg log messages to the error section
-                report.add_error(repo_name, archive_glob, e.to_string());
+                if repo.min_free_bytes.is_some() || repo.min_free_percent.is_some() {
+                    report.add_warning(
+                        &repo.name,
+                        None,
+                        format!("Cannot determine the free space of the repository: {e}"),
+                    );
+                }
+                return report;
+            }
+        };
+        report.free_space.insert(repo.name.clone(), available);
+        if let Some(min_free_bytes) = repo.min_free_bytes.filter(|min| available < *min) {
+            report.add_warning(
+                &repo.name,
+                None,
+                format!(
+                    "Only {} available on the filesystem of the repository, which is below the threshold of {}",
+                    available.human_count_bytes(),
+                    min_free_bytes.human_count_bytes()
+                ),
+            );
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let free_percent = (total > 0).then(|| available as f64 / total as f64 * 100.0);
+        if let (Some(free_percent), Some(min_free_percent)) = (free_percent, repo.min_free_percent)
+        {
+            if free_percent < min_free_percent {
+                report.add_warning(
+                    &repo.name,
+                    None,
+                    format!(
+                        "Only {free_percent:.1}% available on the filesystem of the repository, which is below the threshold of {min_free_percent}%"
+                    ),
+                );
+            }
+        }
+        report
+    }
+
+    /// Warn when archives share the same start time or started before their predecessor.
+    /// This indicates a stuck or wrong clock on the backup source.
+    /// The order is only checked for archives selected by time, as archives selected by name are sorted by name.
+    pub fn from_clock_check(
+        repo_name: &str,
+        archive_glob: Option<&str>,
+        info: &borg::Info,
+        last_by: cli::LastBy,
+    ) -> Self {
+        let mut report = Self::new();
+        if last_by == cli::LastBy::Time {
+            for pair in info.archives.windows(2) {
+                let (previous, archive) = (&pair[0], &pair[1]);
+                if archive.start < previous.start {
+                    report.add_warning(
+                        repo_name,
+                        archive_glob,
+                        format!(
+                            "Archive {} started before its predecessor {}. The clock of the backup source may be wrong.",
+                            archive.name, previous.name
+                        ),
+                    );
+                }
+            }
+        }
+
+        // Archives with the same start time are neighbours once sorted by start
+        let mut archives: Vec<&borg::Archive> = info.archives.iter().collect();
+        archives.sort_by_key(|archive| archive.start);
+        let mut duplicates: Vec<&str> = Vec::new();
+        for pair in archives.windows(2) {
+            let (previous, archive) = (pair[0], pair[1]);
+            if archive.start == previous.start {
+                if !duplicates.contains(&previous.name.as_str()) {
+                    duplicates.push(&previous.name);
+                }
+                duplicates.push(&archive.name);
+            }
+        }
+        if !duplicates.is_empty() {
+            report.add_warning(
+                repo_name,
+                archive_glob,
+                format!(
+                    "Archives share the same start time: {}. The clock of the backup source may be stuck.",
+                    duplicates.join(", ")
+                ),
+            );
+        }
+        report
+    }
+
+    /// Warn when the repository was not modified in the last `stale_runs` runs
+    pub fn from_stale_runs(
+        repo_name: &str,
+        unmodified_runs: usize,
+        stale_runs: Option<usize>,
+        last_modified: jiff::civil::DateTime,
+    ) -> Self {
+        let mut report = Self::new();
+        if stale_runs.is_some_and(|n| unmodified_runs >= n) {
+            report.add_warning(
+                repo_name,
+                None,
+                format!(
+                    "The repository was not modified in the last {unmodified_runs} runs (since {})",
+                    jiff::fmt::strtime::format("%F %T", last_modified).unwrap_or_default()
+                ),
+            );
+        }
+        report
+    }
+
+    /// Raise an error when the `last_modified` time of the repository manifest moved backward
+    /// since the `previous` run. This can indicate a rollback or a replaced repository.
+    pub fn from_rollback_check(
+        repo_name: &str,
+        previous: Option<jiff::civil::DateTime>,
+        last_modified: jiff::civil::DateTime,
+    ) -> Self {
+        let mut report = Self::new();
+        if let Some(previous) = previous.filter(|previous| last_modified < *previous) {
+            let format = |t| jiff::fmt::strtime::format("%F %T", t).unwrap_or_default();
+            report.add_error(
+                repo_name,
+                None,
+                format!(
+                    "The repository manifest moved backward from {} to {} since the previous run. The repository may have been rolled back or replaced!",
+                    format(previous),
+                    format(last_modified)
+                ),
+            );
+        }
+        report
+    }
+
+    /// Warn when the `last_success` of `borg check` is unknown or older than `max_interval_hours`
+    pub fn from_check_interval(
+        repo_name: &str,
+        last_success: Option<jiff::Timestamp>,
+        max_interval_hours: Option<f64>,
+    ) -> Self {
+        let mut report = Self::new();
+        if let Some(last_success) = last_success {
+            report
+                .last_check_success
+                .insert(repo_name.to_string(), last_success);
+        }
+        if let Some(max_interval_hours) = max_interval_hours {
+            match last_success
+                .map(|t| jiff::Timestamp::now().duration_since(t).as_secs_f64() / 3600.0)
+            {
+                Some(hours) if hours <= max_interval_hours => {}
+                Some(_) => report.add_warning(
+                    repo_name,
+                    None,
+                    format!(
+                        "Last successful `borg check` is older than {max_interval_hours} hours"
+                    ),
+                ),
+                None => report.add_warning(
+                    repo_name,
+                    None,
+                    "No successful `borg check` is known in the state",
+                ),
             }
         }
         report
@@ -169,14 +850,18 @@ impl Report {
         archive_glob: Option<&str>,
         info: &borg::Info,
         max_age_hours: f64,
+        allow_empty: bool,
+        expected_hostname: Option<&str>,
     ) -> Self {
         let mut report = Self::new();
-        // warn if there are no backup archives (skip remaining tests)
+        // warn if there are no backup archives unless allowed (skip remaining tests)
         if info.archives.is_empty() {
-            report.add_warning(repo_name, archive_glob, "Repository is empty");
+            if !allow_empty {
+                report.add_warning(repo_name, archive_glob, "Repository is empty");
+            }
         } else {
-            for a in &info.archives {
-                // warn if the backup age is too old
+            // warn if the newest backup age is too old (the archives are sorted by time)
+            if let Some(a) = info.archives.last() {
                 if let Ok(span) = a
                     .start
                     .until(jiff::Timestamp::now().to_zoned(jiff::tz::TimeZone::UTC))
@@ -199,14 +884,27 @@ impl Report {
                         ),
                     );
                 }
-                // warn if backup Source is empty
-                if a.stats.original_size == 0 {
+            }
+            // warn if backup Source of the newest archive is empty
+            if let Some(a) = info.archives.last().filter(|a| a.stats.original_size == 0) {
+                report.add_warning(
+                    repo_name,
+                    archive_glob,
+                    format!(
+                        "Last backup archive contains no data. Archive {} is empty.",
+                        a.name
+                    ),
+                );
+            }
+            for a in &info.archives {
+                // warn if the archive was created on another host than expected for the glob
+                if let Some(expected) = expected_hostname.filter(|h| *h != a.hostname) {
                     report.add_warning(
                         repo_name,
                         archive_glob,
                         format!(
-                            "Last backup archive contains no data. Archive {} is empty.",
-                            a.name
+                            "Archive {} was created on host {} instead of the expected host {expected}",
+                            a.name, a.hostname
                         ),
                     );
                 }
@@ -236,13 +934,15 @@ fn add_msg_prefix(repository: &str, archive_glob: Option<&str>, msg: impl Into<S
 }
 
 /// A data point with reference to its origin
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, JsonSchema)]
+#[schemars(rename = "{T}Record")]
 pub(crate) struct Record<T>
 where
     T: PartialEq + Clone,
 {
     pub(crate) repository: String,
     pub(crate) archive_glob: Option<String>,
+    #[serde(flatten)]
     inner: T,
 }
 
@@ -286,6 +986,8 @@ where
 pub type SectionInner<T> = Vec<Record<T>>;
 
 /// A section holds a list of content T
+#[derive(Serialize, JsonSchema)]
+#[schemars(rename = "{T}Section")]
 pub(crate) struct Section<T>(SectionInner<T>)
 where
     T: PartialEq + Clone;
@@ -331,7 +1033,7 @@ where
     /// ```rust
     /// add(("repo", None, BulletPoint::from("Text")))
     /// ```
-    fn add<R>(&mut self, record: R)
+    pub(crate) fn add<R>(&mut self, record: R)
     where
         R: Into<Record<T>>,
     {
@@ -369,6 +1071,25 @@ impl From<String> for BulletPoint {
     }
 }
 
+impl Record<BulletPoint> {
+    /// The origin as "`repo`\[`archive_glob`\]"
+    pub(crate) fn origin(&self) -> String {
+        format!(
+            "{}{}",
+            self.repository,
+            self.archive_glob
+                .as_ref()
+                .map_or(String::default(), |glob| format!("[{glob}]"))
+        )
+    }
+
+    /// The message without the "`repo`\[`archive_glob`\]: " prefix
+    pub(crate) fn message(&self) -> &str {
+        let prefix = add_msg_prefix(&self.repository, self.archive_glob.as_deref(), "");
+        self.strip_prefix(prefix.as_str()).unwrap_or(self)
+    }
+}
+
 /// A `Section` with a list of `BulletPoints`
 impl Section<BulletPoint> {
     /// Add a String value as new `BulletPoint`
@@ -378,15 +1099,19 @@ impl Section<BulletPoint> {
 }
 
 /// A single summary entry
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, JsonSchema)]
 pub(crate) struct SummaryEntry {
     /// Name of the backup archive
     pub(crate) archive: String,
     /// Hostname on which the backup was taken
     pub(crate) hostname: String,
     /// Duration the backup has taken
+    #[serde(serialize_with = "crate::format::json::seconds")]
+    #[schemars(with = "f64")]
     pub(crate) duration: jiff::SignedDuration,
     /// Time when backup was started
+    #[serde(serialize_with = "crate::format::json::timestamp")]
+    #[schemars(with = "crate::format::json::Timestamp")]
     pub(crate) start: jiff::civil::DateTime,
     /// Total original archive size (size of backup source)
     pub(crate) original_size: i64,
@@ -398,15 +1123,95 @@ pub(crate) struct SummaryEntry {
     pub(crate) nfiles: i64,
     /// Total deduplicated compressed repository size
     pub(crate) unique_csize: i64,
+    /// Total original size of all archives in the repository
+    pub(crate) total_size: i64,
+    /// Total deduplicated repository size (not compressed)
+    pub(crate) unique_size: i64,
+    /// Growth of the total repository size since the previous run
+    pub(crate) growth: Option<i64>,
+    /// Encryption mode of the repository
+    pub(crate) encryption: String,
+    /// Time when the oldest backup was started
+    #[serde(serialize_with = "crate::format::json::optional_timestamp")]
+    #[schemars(with = "Option<crate::format::json::Timestamp>")]
+    pub(crate) oldest: Option<jiff::civil::DateTime>,
+    /// Change of the original size since the previous archive in the summary
+    pub(crate) original_size_delta: Option<i64>,
+    /// Named tier of the archive glob
+    pub(crate) tier: Option<String>,
+    /// Command line, which created the archive
+    pub(crate) command_line: String,
+    /// Comment of the archive e.g. "pre-upgrade snapshot"
+    pub(crate) comment: String,
+}
+impl SummaryEntry {
+    /// Ratio of the total original size to the deduplicated size of the repository e.g. `8.0`
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn dedup_ratio(&self) -> Option<f64> {
+        (self.unique_size > 0).then(|| self.total_size as f64 / self.unique_size as f64)
+    }
+
+    /// Share of the total original size saved by deduplication and compression e.g. `0.875`
+    pub(crate) fn savings(&self) -> Option<f64> {
+        savings_ratio(self.total_size, self.unique_csize)
+    }
 }
+
+/// Share of the `total_size` saved, when stored as `unique_csize`
+#[allow(clippy::cast_precision_loss)]
+fn savings_ratio(total_size: i64, unique_csize: i64) -> Option<f64> {
+    (total_size > 0).then(|| 1.0 - unique_csize as f64 / total_size as f64)
+}
+
 impl Section<SummaryEntry> {
+    /// Assign the named tier to all entries by their archive glob
+    pub(crate) fn set_tiers(&mut self, tiers: &BTreeMap<String, String>) {
+        for entry in &mut self.0 {
+            entry.inner.tier = entry
+                .archive_glob
+                .as_ref()
+                .and_then(|glob| tiers.get(glob))
+                .cloned();
+        }
+    }
+
+    /// Aggregate the summary by the hostname of the last archives
+    pub(crate) fn rollup_by_host(&self) -> HostRollup {
+        let mut hosts: std::collections::BTreeMap<&str, (HostRollupEntry, Vec<&str>)> =
+            std::collections::BTreeMap::new();
+        for entry in self.inner().iter().filter(|e| !e.archive.is_empty()) {
+            let (host, repositories) = hosts.entry(&entry.hostname).or_default();
+            host.archives += 1;
+            host.original_size += entry.original_size;
+            host.deduplicated_size += entry.deduplicated_size;
+            // Count the size of each repository once, even with multiple archive globs
+            if !repositories.contains(&entry.repository.as_str()) {
+                repositories.push(&entry.repository);
+                host.unique_csize += entry.unique_csize;
+            }
+        }
+        HostRollup(
+            hosts
+                .into_iter()
+                .map(|(hostname, (host, repositories))| HostRollupEntry {
+                    hostname: hostname.to_string(),
+                    repositories: repositories.len(),
+                    ..host
+                })
+                .collect(),
+        )
+    }
+
     /// Extract and add summary entries from a borg info response
     fn add_from_borg_info(
         &mut self,
         repo_name: &str,
         archive_glob: Option<&str>,
         info: &borg::Info,
+        previous_unique_csize: Option<i64>,
+        oldest: Option<jiff::civil::DateTime>,
     ) {
+        let growth = previous_unique_csize.map(|previous| info.cache.stats.unique_csize - previous);
         // Add an default entry in case the repository has no archives
         if info.archives.is_empty() {
             self.add((
@@ -414,15 +1219,22 @@ impl Section<SummaryEntry> {
                 archive_glob,
                 SummaryEntry {
                     unique_csize: info.cache.stats.unique_csize,
+                    total_size: info.cache.stats.total_size,
+                    unique_size: info.cache.stats.unique_size,
+                    growth,
+                    encryption: info.encryption.mode.clone(),
                     ..Default::default()
                 },
             ));
         // Add a line for each repository in the archive
+        // The first archive has no predecessor to compare the original size with
         } else {
+            let predecessors = std::iter::once(None).chain(info.archives.iter().map(Some));
             self.append(
                 info.archives
                     .iter()
-                    .map(|a| Record {
+                    .zip(predecessors)
+                    .map(|(a, predecessor)| Record {
                         repository: repo_name.to_string(),
                         archive_glob: archive_glob.map(ToString::to_string),
                         inner: SummaryEntry {
@@ -435,6 +1247,16 @@ impl Section<SummaryEntry> {
                             deduplicated_size: a.stats.deduplicated_size,
                             nfiles: a.stats.nfiles,
                             unique_csize: info.cache.stats.unique_csize,
+                            total_size: info.cache.stats.total_size,
+                            unique_size: info.cache.stats.unique_size,
+                            growth,
+                            encryption: info.encryption.mode.clone(),
+                            oldest,
+                            original_size_delta: predecessor
+                                .map(|p| a.stats.original_size - p.stats.original_size),
+                            tier: None,
+                            command_line: a.command_line.join(" "),
+                            comment: a.comment.clone(),
                         },
                     })
                     .collect(),
@@ -443,11 +1265,202 @@ impl Section<SummaryEntry> {
     }
 }
 
-/// A single check entry (result of `borg check`)
+/// Aggregated summary per hostname
+pub(crate) struct HostRollup(pub(crate) Vec<HostRollupEntry>);
+
+/// A single host entry of the `HostRollup`
 #[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct HostRollupEntry {
+    /// Hostname on which the backups were taken
+    pub(crate) hostname: String,
+    /// Number of repositories with an archive of the host
+    pub(crate) repositories: usize,
+    /// Number of last archives of the host
+    pub(crate) archives: usize,
+    /// Total original size of the last archives
+    pub(crate) original_size: i64,
+    /// Total deduplicated and compressed size of the last archives
+    pub(crate) deduplicated_size: i64,
+    /// Total deduplicated compressed size of the repositories
+    pub(crate) unique_csize: i64,
+}
+
+/// A single check entry (result of `borg check`)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, JsonSchema)]
 pub(crate) struct ChecksEntry {
+    #[serde(skip)]
     pub(crate) repository: String,
     pub(crate) archive_name: Option<String>,
+    #[serde(serialize_with = "crate::format::json::seconds")]
+    #[schemars(with = "f64")]
     pub(crate) duration: jiff::SignedDuration,
+    #[serde(
+        rename = "exit_code",
+        serialize_with = "crate::format::json::exit_code"
+    )]
+    #[schemars(with = "Option<i32>")]
     pub(crate) status: std::process::ExitStatus,
+    /// Time when the check finished
+    #[schemars(with = "crate::format::json::Timestamp")]
+    pub(crate) timestamp: jiff::Timestamp,
+    /// Change of the outcome since the previous check of the repository
+    pub(crate) change: Option<CheckChange>,
+    /// Peak memory usage (max RSS) of `borg check` in bytes
+    pub(crate) peak_rss: Option<u64>,
+}
+
+/// A single prune entry (result of `borg prune`)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, JsonSchema)]
+pub(crate) struct PruneEntry {
+    #[serde(skip)]
+    pub(crate) repository: String,
+    #[serde(serialize_with = "crate::format::json::seconds")]
+    #[schemars(with = "f64")]
+    pub(crate) duration: jiff::SignedDuration,
+    #[serde(
+        rename = "exit_code",
+        serialize_with = "crate::format::json::exit_code"
+    )]
+    #[schemars(with = "Option<i32>")]
+    pub(crate) status: std::process::ExitStatus,
+    /// Number of pruned archives
+    pub(crate) pruned: usize,
+    /// Number of kept archives
+    pub(crate) kept: usize,
+    /// Deduplicated size of the deleted data in bytes (negative)
+    pub(crate) deleted_size: Option<i64>,
+}
+
+/// A change of the outcome of `borg check` since the previous check
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CheckChange {
+    /// The previous check passed but this one failed
+    Failing,
+    /// The previous check failed but this one passed
+    Recovered,
+}
+impl CheckChange {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Failing => "newly failing",
+            Self::Recovered => "recovered",
+        }
+    }
+}
+
+impl ChecksEntry {
+    /// The exit code of `borg check` or the signal, which terminated it e.g. `signal 9`
+    pub(crate) fn exit_code(&self) -> String {
+        use std::os::unix::process::ExitStatusExt;
+        match (self.status.code(), self.status.signal()) {
+            (Some(code), _) => code.to_string(),
+            (None, Some(signal)) => format!("signal {signal}"),
+            (None, None) => String::default(),
+        }
+    }
+}
+
+mod tests {
+    #[test]
+    fn total_sizes() {
+        use super::{Report, SummaryEntry};
+
+        let entry = |total_size, unique_csize| SummaryEntry {
+            unique_csize,
+            total_size,
+            ..SummaryEntry::default()
+        };
+        let mut report = Report::new();
+        // Two virtual repositories of the same physical repository and another repository
+        for name in ["nas/host1", "nas/host2"] {
+            report.parents.insert(name.to_string(), "nas".to_string());
+            report.summary.add((name, None::<String>, entry(400, 100)));
+        }
+        report.summary.add(("usb", None::<String>, entry(200, 100)));
+
+        assert_eq!(report.total_unique_csize(), 200);
+        assert_eq!(report.savings().map(|(t, u, _)| (t, u)), Some((600, 200)));
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn empty_last_archive() {
+        use super::Report;
+
+        let archive = |name: &str, original_size: i64| {
+            serde_json::json!({
+                "hostname": "host",
+                "name": name,
+                "duration": 1.0,
+                "start": "2024-08-06T01:48:43",
+                "stats": {
+                    "original_size": original_size,
+                    "compressed_size": 0,
+                    "deduplicated_size": 0,
+                    "nfiles": 0
+                }
+            })
+        };
+        let info = |archives: Vec<serde_json::Value>| {
+            serde_json::from_value(serde_json::json!({
+                "archives": archives,
+                "cache": { "stats": { "total_size": 0, "unique_size": 0, "unique_csize": 0 } },
+                "encryption": { "mode": "repokey" },
+                "repository": { "last_modified": "2024-08-06T01:48:43" }
+            }))
+            .unwrap()
+        };
+        let warnings = |info| {
+            Report::from_sanity_checks("repo", None, &info, f64::MAX, false, None).count_warnings()
+        };
+
+        // Only the newest archive (the archives are sorted by time) is checked for data
+        assert_eq!(
+            warnings(info(vec![archive("old", 0), archive("new", 1)])),
+            0
+        );
+        assert_eq!(
+            warnings(info(vec![archive("old", 1), archive("new", 0)])),
+            1
+        );
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn clock_check() {
+        use super::Report;
+        use crate::cli::LastBy;
+
+        let archive = |name: &str, start: &str| {
+            serde_json::json!({
+                "hostname": "host",
+                "name": name,
+                "duration": 1.0,
+                "start": start,
+                "stats": { "original_size": 0, "compressed_size": 0, "deduplicated_size": 0, "nfiles": 0 }
+            })
+        };
+        let info = serde_json::from_value(serde_json::json!({
+            "archives": [
+                archive("a", "2024-08-06T01:00:00"),
+                archive("b", "2024-08-05T01:00:00"),
+                archive("c", "2024-08-06T01:00:00"),
+            ],
+            "cache": { "stats": { "total_size": 0, "unique_size": 0, "unique_csize": 0 } },
+            "encryption": { "mode": "repokey" },
+            "repository": { "last_modified": "2024-08-06T01:00:00" }
+        }))
+        .unwrap();
+
+        // The duplicates "a" and "c" are found in any order, the order only matters by time
+        let by_name = Report::from_clock_check("repo", None, &info, LastBy::Name);
+        assert_eq!(by_name.count_warnings(), 1);
+        assert!(by_name
+            .warnings
+            .iter()
+            .any(|w| w.message().contains("a, c")));
+        let by_time = Report::from_clock_check("repo", None, &info, LastBy::Time);
+        assert_eq!(by_time.count_warnings(), 2);
+    }
 }
@@ -1,40 +1,120 @@
 // SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
 
 use crate::{
     borg::{self, Env},
-    cli,
+    cli::{self, CheckRepair, LastBy, MailOn, Severity},
 };
-use anyhow::{anyhow, ensure, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 
 /// BORGREPORT_* env vars used on `Repository` level
 /// These need to match a clap `ArgId` to allow overriding via cli option.
 /// These must not have a clap `env` or it will overrule the repo config.
 mod args {
     pub(super) use crate::cli::args::{
-        BORG_BINARY, CHECK, CHECK_OPTIONS, GLOB_ARCHIVES, MAX_AGE_HOURS,
+        ALIASES, ALLOW_EMPTY, ANOMALY_SIGMA, BORG_BINARY, BORG_VERSION_REQ, BYPASS_LOCK, CHECK,
+        CHECK_IGNORE_PATTERNS, CHECK_MAX_INTERVAL_HOURS, CHECK_OPTIONS, CHECK_OPTIONS_APPEND,
+        CHECK_REPAIR, CHECK_SAMPLE, CHECK_SCHEDULE, CHECK_TIMEOUT, CLOCK_CHECK, CRITICAL,
+        GLOB_ARCHIVES, GLOB_HOSTNAMES, GLOB_LABELS, INFO_OPTIONS, LAST_ARCHIVES, LAST_BY, LOG_JSON,
+        MAIL_ON, MAX_AGE_HOURS, MAX_GROWTH_PERCENT, MIN_FREE_BYTES, MIN_FREE_PERCENT, PRIMARY_GLOB,
+        PRUNE, PRUNE_OPTIONS, QUOTA_WARN_PERCENT, RELOCATED_REPO_ACCESS_IS_OK,
+        REPO_TIMEOUT_AS_WARNING, ROLLBACK_CHECK, SEVERITY, STALE_RUNS, TIERS,
+        UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK, VIRTUAL_REPOS,
     };
 }
 
 /// A `Repository` describes the access parameters for a borg repository
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Clone, Debug)]
 pub struct Repository {
     /// Name of the repository
     pub name: String,
     /// Collection of BORG_* env vars required to access the Repository
     pub env: borg::Env,
-    /// The borg binary path
-    pub borg_binary: PathBuf,
+    /// The configured borg binary path
+    pub borg_binary: Option<PathBuf>,
+    /// Version requirement of the borg binary, which is resolved before `borg` runs
+    pub borg_version_req: Option<semver::VersionReq>,
     /// list of given archive globs
     pub archive_globs: Vec<String>,
+    /// Friendly names of the archive globs used as metric labels
+    pub glob_labels: BTreeMap<String, String>,
+    /// Expected hostname of the archives per archive glob
+    pub glob_hostnames: BTreeMap<String, String>,
+    /// Named tiers of the archive globs
+    pub tiers: BTreeMap<String, String>,
+    /// The archive glob displayed in the summary
+    pub primary_glob: Option<String>,
+    /// Virtual repositories by name and archive glob sharing this repository
+    pub virtual_repos: Vec<(String, String)>,
+    /// Name of the physical repository of a virtual repository `<REPO>/<NAME>`
+    pub parent: Option<String>,
+    /// Number of the newest archives to show per archive glob
+    pub last_archives: usize,
+    /// Order to select the newest archives
+    pub last_by: LastBy,
     /// True if `borg check` shall run
     pub run_check: bool,
     /// List of additional raw `borg check` options
     pub check_options: Vec<String>,
+    /// Policy for modifying options of `borg check` e.g. `--repair`
+    pub check_repair: CheckRepair,
+    /// True if `borg prune` shall run
+    pub run_prune: bool,
+    /// Raw `borg prune` options
+    pub prune_options: Vec<String>,
+    /// List of additional raw `borg info` options
+    pub info_options: Vec<String>,
+    /// Days to run `borg check` or any day for `None`
+    pub check_schedule: Option<Schedule>,
+    /// Number of random archives to check instead of the last archive
+    pub check_sample: Option<usize>,
+    /// Output lines of `borg check` matching any pattern are ignored
+    pub check_ignore_patterns: Vec<regex::Regex>,
+    /// Duration after which `borg check` is terminated
+    pub check_timeout: Option<std::time::Duration>,
+    /// True if connection failures and timeouts are reported as warnings
+    pub timeout_as_warning: bool,
     /// Threshold for the sanity check to alert, when an archive is older
     pub max_age_hours: f64,
+    /// Threshold to alert, when the last successful `borg check` is older
+    pub check_max_interval_hours: Option<f64>,
+    /// True if an empty repository is no reason to warn
+    pub allow_empty: bool,
+    /// True if the archive start times are checked for a stuck clock
+    pub clock_check: bool,
+    /// True if the manifest of the repository is checked for a rollback since the previous run
+    pub rollback_check: bool,
+    /// True if an error in the repository is critical for the aggregated status
+    pub critical: bool,
+    /// Maximum severity of the issues in the aggregated status and the mail policy
+    pub severity: Severity,
+    /// True if `borg` shall emit structured log messages
+    pub log_json: bool,
+    /// Threshold for the growth check to alert, when the repository grew more since the previous run
+    pub max_growth_percent: Option<f64>,
+    /// Threshold for the anomaly check to alert, when the repository size deviates more from its moving average
+    pub anomaly_sigma: Option<f64>,
+    /// Threshold to alert, when the filesystem of a local repository has less bytes available
+    pub min_free_bytes: Option<u64>,
+    /// Threshold to alert, when the filesystem of a local repository has a smaller share available
+    pub min_free_percent: Option<f64>,
+    /// Threshold to alert, when the repository uses a larger share of its storage quota
+    pub quota_warn_percent: Option<f64>,
+    /// Threshold to alert, when the repository was not modified in this number of runs
+    pub stale_runs: Option<usize>,
+    /// Policy when the repository requires to send the report per mail
+    pub mail_on: MailOn,
+    /// True if `borg info` shall ignore the repository lock
+    pub bypass_lock: bool,
+    /// True if `borg` may access an unknown unencrypted repository without confirmation
+    pub unknown_unencrypted_repo_access_is_ok: bool,
+    /// True if `borg` may access a relocated repository without confirmation
+    pub relocated_repo_access_is_ok: bool,
+    /// Problems of the configuration, which are reported as warnings e.g. an unknown variable
+    pub config_warnings: Vec<String>,
 }
 impl Repository {
     /// Parse an env file into a `Repository` configuration.
@@ -56,13 +136,47 @@ impl Repository {
             .into_iter()
             .collect();
 
+        let unknown_keys = unknown_keys(&env);
+        let mut repo = Self::from_env(repo_name, env)?;
+        repo.config_warnings.extend(
+            unknown_keys
+                .into_iter()
+                .map(|key| format!("Unknown variable {key} in the env file {file:?}")),
+        );
+        Ok(repo)
+    }
+
+    /// Construct a `Repository` in keyfile mode from a key `file`.
+    /// The repo name is the file name without its extension and replaces `{name}` in the `location`.
+    pub fn from_key_file(file: &std::path::Path, location: &str) -> Result<Self> {
+        let repo_name = file
+            .file_stem()
+            .and_then(std::ffi::OsStr::to_str)
+            .context(format!("Key file {file:?} has no valid filename"))?
+            .to_string();
+        std::fs::File::open(file).context(format!("Cannot read key file {file:?}"))?;
+        let key_file = file
+            .to_str()
+            .context(format!("Key file {file:?} has no valid path"))?;
+
+        let env = [
+            ("BORG_REPO", location.replace("{name}", &repo_name)),
+            ("BORG_KEY_FILE", key_file.to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
         Self::from_env(repo_name, env)
     }
 
     /// Construct a `Repository` with a list of `env` vars (BORG_*).
     /// The CLI options and global ENV are evaluated in addition.
+    #[allow(clippy::too_many_lines)]
     pub fn from_env(repo_name: String, env: borg::Env) -> Result<Self> {
         let name = repo_name;
+        // The BORG_* vars of the CLI have the lowest precedence, later entries overwrite
+        let env: borg::Env = cli::args().borg_env.iter().cloned().chain(env).collect();
 
         // Get the args with some added error context
         macro_rules! arg_error_context {
@@ -70,26 +184,108 @@ impl Repository {
                 arg(&env, $arg)
                     .context(format!("Cannot parse parameter {} for repo {name}", $arg))?
             };
+            // Parse the text value of the arg with a fallible `$parse` function
+            ($arg: path, $parse: expr) => {
+                arg(&env, $arg)
+                    .and_then(|value: Option<String>| {
+                        value
+                            .map(|v| $parse(&v).map_err(|e| anyhow!(e)))
+                            .transpose()
+                    })
+                    .context(format!("Cannot parse parameter {} for repo {name}", $arg))?
+            };
         }
 
         // Provide default values
-        let borg_binary = arg_error_context!(args::BORG_BINARY).unwrap_or(PathBuf::from("borg"));
-        let run_check = arg_error_context!(args::CHECK).unwrap_or(false);
+        let borg_binary: Option<PathBuf> = arg_error_context!(args::BORG_BINARY);
+        let borg_version_req =
+            arg_error_context!(args::BORG_VERSION_REQ, semver::VersionReq::parse);
+        // The fast mode skips `borg check` and `borg prune`
+        let run_check = arg_error_context!(args::CHECK).unwrap_or(false) && !cli::args().fast;
+        let prune = arg_error_context!(args::PRUNE).unwrap_or(false);
+        let run_prune = prune && !cli::args().fast;
+        let prune_options =
+            arg_error_context!(args::PRUNE_OPTIONS, parse_options).unwrap_or_default();
+        ensure!(
+            !prune || has_keep_rule(&prune_options),
+            "Cannot parse parameter {} for repo {name}: `borg prune` requires at least one --keep-* rule e.g. \"--keep-daily 7\"",
+            args::PRUNE_OPTIONS
+        );
         let max_age_hours = arg_error_context!(args::MAX_AGE_HOURS).unwrap_or(24.0);
-        let archive_globs =
-            arg_error_context!(args::GLOB_ARCHIVES).map_or(Vec::new(), |globs: String| {
-                globs
-                    .split_whitespace()
-                    .map(std::string::String::from)
-                    .collect()
-            });
-        let check_options =
-            arg_error_context!(args::CHECK_OPTIONS).map_or(Vec::new(), |opts: String| {
-                opts.split_whitespace()
-                    .map(std::string::String::from)
-                    .collect()
-            });
+        let check_max_interval_hours = arg_error_context!(args::CHECK_MAX_INTERVAL_HOURS);
+        let allow_empty = arg_error_context!(args::ALLOW_EMPTY).unwrap_or(false);
+        let clock_check = arg_error_context!(args::CLOCK_CHECK).unwrap_or(false);
+        let rollback_check = arg_error_context!(args::ROLLBACK_CHECK).unwrap_or(false);
+        let critical = arg_error_context!(args::CRITICAL).unwrap_or(false);
+        let severity = arg_error_context!(args::SEVERITY).unwrap_or_default();
+        let timeout_as_warning = arg_error_context!(args::REPO_TIMEOUT_AS_WARNING).unwrap_or(false);
+        let last_archives = arg_error_context!(args::LAST_ARCHIVES).unwrap_or(1);
+        let last_by = arg_error_context!(args::LAST_BY).unwrap_or_default();
+        let log_json = arg_error_context!(args::LOG_JSON).unwrap_or(false);
+        let max_growth_percent = arg_error_context!(args::MAX_GROWTH_PERCENT);
+        let anomaly_sigma = arg_error_context!(args::ANOMALY_SIGMA);
+        let min_free_bytes = arg_error_context!(args::MIN_FREE_BYTES);
+        let min_free_percent = arg_error_context!(args::MIN_FREE_PERCENT);
+        let quota_warn_percent = arg_error_context!(args::QUOTA_WARN_PERCENT);
+        let stale_runs = arg_error_context!(args::STALE_RUNS);
+        let check_sample = arg_error_context!(args::CHECK_SAMPLE);
+        let check_schedule = arg_error_context!(args::CHECK_SCHEDULE, parse_schedule);
+        let check_timeout = arg_error_context!(args::CHECK_TIMEOUT, cli::parse_interval);
+        let check_ignore_patterns =
+            arg_error_context!(args::CHECK_IGNORE_PATTERNS, parse_patterns).unwrap_or_default();
+        let mail_on = arg_error_context!(args::MAIL_ON).unwrap_or_default();
+        let bypass_lock = arg_error_context!(args::BYPASS_LOCK).unwrap_or(true);
+        let unknown_unencrypted_repo_access_is_ok =
+            arg_error_context!(args::UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK).unwrap_or(false);
+        let relocated_repo_access_is_ok =
+            arg_error_context!(args::RELOCATED_REPO_ACCESS_IS_OK).unwrap_or(false);
+        let mut archive_globs = arg_error_context!(args::GLOB_ARCHIVES)
+            .map_or(Vec::new(), |globs: String| split_options(&globs));
+        let glob_labels =
+            arg_error_context!(args::GLOB_LABELS, parse_glob_labels).unwrap_or_default();
+        let glob_hostnames =
+            arg_error_context!(args::GLOB_HOSTNAMES, parse_glob_hostnames).unwrap_or_default();
+        let tiers = arg_error_context!(args::TIERS, parse_tiers).unwrap_or_default();
+        // Each tier adds its glob to the archive globs
+        for glob in tiers.keys() {
+            if !archive_globs.contains(glob) {
+                archive_globs.push(glob.clone());
+            }
+        }
+        let primary_glob = arg_error_context!(args::PRIMARY_GLOB, |glob: &str| {
+            parse_primary_glob(glob, &archive_globs)
+        });
+        let virtual_repos =
+            arg_error_context!(args::VIRTUAL_REPOS, parse_virtual_repos).unwrap_or_default();
+        let info_options =
+            arg_error_context!(args::INFO_OPTIONS, parse_info_options).unwrap_or_default();
+        // The appended options follow the (overridable) repository options
+        let check_repair = arg_error_context!(args::CHECK_REPAIR).unwrap_or_default();
+        let (check_options, mut config_warnings) = guard_check_options(
+            append_check_options(
+                arg_error_context!(args::CHECK_OPTIONS, parse_options).unwrap_or_default(),
+            )?,
+            check_repair,
+        );
+        config_warnings.extend(guard_state_options(
+            cli::args().state_dir.is_some(),
+            &[
+                (
+                    args::CHECK_MAX_INTERVAL_HOURS,
+                    check_max_interval_hours.is_some(),
+                ),
+                (args::MAX_GROWTH_PERCENT, max_growth_percent.is_some()),
+                (args::ANOMALY_SIGMA, anomaly_sigma.is_some()),
+                (args::STALE_RUNS, stale_runs.is_some()),
+                (args::ROLLBACK_CHECK, rollback_check),
+            ],
+        ));
 
+        ensure!(
+            last_archives > 0,
+            "Cannot parse parameter {} for repo {name}: The value must be at least 1",
+            args::LAST_ARCHIVES
+        );
         ensure!(
             env.get("BORG_REPO").is_some_and(|v| !v.is_empty()),
             "No value for 'BORG_REPO' was provided for repository: '{name}'"
@@ -99,14 +295,555 @@ impl Repository {
             name,
             env,
             borg_binary,
+            borg_version_req,
             archive_globs,
+            glob_labels,
+            glob_hostnames,
+            tiers,
+            primary_glob,
+            virtual_repos,
+            parent: None,
+            last_archives,
+            last_by,
             run_check,
             check_options,
+            check_repair,
+            run_prune,
+            prune_options,
+            info_options,
+            check_schedule,
+            check_sample,
+            check_ignore_patterns,
+            check_timeout,
+            timeout_as_warning,
             max_age_hours,
+            check_max_interval_hours,
+            allow_empty,
+            clock_check,
+            rollback_check,
+            critical,
+            severity,
+            log_json,
+            max_growth_percent,
+            anomaly_sigma,
+            min_free_bytes,
+            min_free_percent,
+            quota_warn_percent,
+            stale_runs,
+            mail_on,
+            bypass_lock,
+            unknown_unencrypted_repo_access_is_ok,
+            relocated_repo_access_is_ok,
+            config_warnings,
         })
     }
 }
 
+impl Repository {
+    /// Split the repository into its virtual repositories `<REPO>/<NAME>` with a single archive glob each
+    pub fn into_virtual_repos(self) -> Vec<Self> {
+        if self.virtual_repos.is_empty() {
+            return vec![self];
+        }
+        self.virtual_repos
+            .iter()
+            .map(|(name, glob)| Self {
+                name: format!("{}/{name}", self.name),
+                archive_globs: vec![glob.clone()],
+                primary_glob: None,
+                virtual_repos: Vec::new(),
+                parent: Some(self.name.clone()),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// The archive globs to prune one by one or `None` to prune all archives without a glob
+    pub fn prune_globs(&self) -> Vec<Option<&str>> {
+        if self.archive_globs.is_empty() {
+            vec![None]
+        } else {
+            self.archive_globs
+                .iter()
+                .map(|g| Some(g.as_str()))
+                .collect()
+        }
+    }
+
+    /// The resolved options as `BORGREPORT_*` variables in the format of an env file.
+    /// Unset options and empty lists are omitted.
+    #[allow(clippy::too_many_lines)]
+    pub fn options(&self) -> Vec<(&'static str, String)> {
+        let flag = |value: bool| Some(value.to_string());
+        let pairs = |pairs: &mut dyn Iterator<Item = (&String, &String)>| {
+            Some(
+                pairs
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+        };
+        [
+            (
+                args::BORG_BINARY,
+                self.borg_binary.as_ref().map(|b| b.display().to_string()),
+            ),
+            (
+                args::BORG_VERSION_REQ,
+                self.borg_version_req.as_ref().map(ToString::to_string),
+            ),
+            (args::GLOB_ARCHIVES, Some(self.archive_globs.join(" "))),
+            (args::GLOB_LABELS, pairs(&mut self.glob_labels.iter())),
+            (args::GLOB_HOSTNAMES, pairs(&mut self.glob_hostnames.iter())),
+            (
+                args::TIERS,
+                pairs(&mut self.tiers.iter().map(|(glob, tier)| (tier, glob))),
+            ),
+            (args::PRIMARY_GLOB, self.primary_glob.clone()),
+            (
+                args::VIRTUAL_REPOS,
+                pairs(&mut self.virtual_repos.iter().map(|(n, g)| (n, g))),
+            ),
+            (args::LAST_ARCHIVES, Some(self.last_archives.to_string())),
+            (args::LAST_BY, value_name(&self.last_by)),
+            (args::CHECK, flag(self.run_check)),
+            (
+                args::CHECK_OPTIONS,
+                Some(format_options(&self.check_options)),
+            ),
+            (args::CHECK_REPAIR, value_name(&self.check_repair)),
+            (args::PRUNE, flag(self.run_prune)),
+            (
+                args::PRUNE_OPTIONS,
+                Some(format_options(&self.prune_options)),
+            ),
+            (args::INFO_OPTIONS, Some(format_options(&self.info_options))),
+            (
+                args::CHECK_SCHEDULE,
+                self.check_schedule.as_ref().map(ToString::to_string),
+            ),
+            (args::CHECK_SAMPLE, self.check_sample.map(|n| n.to_string())),
+            (
+                args::CHECK_IGNORE_PATTERNS,
+                Some(
+                    self.check_ignore_patterns
+                        .iter()
+                        .map(regex::Regex::as_str)
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+            ),
+            (
+                args::CHECK_TIMEOUT,
+                self.check_timeout.map(|t| format!("{}s", t.as_secs())),
+            ),
+            (args::REPO_TIMEOUT_AS_WARNING, flag(self.timeout_as_warning)),
+            (args::MAX_AGE_HOURS, Some(self.max_age_hours.to_string())),
+            (
+                args::CHECK_MAX_INTERVAL_HOURS,
+                self.check_max_interval_hours.map(|h| h.to_string()),
+            ),
+            (args::ALLOW_EMPTY, flag(self.allow_empty)),
+            (args::CLOCK_CHECK, flag(self.clock_check)),
+            (args::ROLLBACK_CHECK, flag(self.rollback_check)),
+            (args::CRITICAL, flag(self.critical)),
+            (args::SEVERITY, value_name(&self.severity)),
+            (args::LOG_JSON, flag(self.log_json)),
+            (
+                args::MAX_GROWTH_PERCENT,
+                self.max_growth_percent.map(|p| p.to_string()),
+            ),
+            (
+                args::ANOMALY_SIGMA,
+                self.anomaly_sigma.map(|s| s.to_string()),
+            ),
+            (
+                args::MIN_FREE_BYTES,
+                self.min_free_bytes.map(|b| b.to_string()),
+            ),
+            (
+                args::MIN_FREE_PERCENT,
+                self.min_free_percent.map(|p| p.to_string()),
+            ),
+            (
+                args::QUOTA_WARN_PERCENT,
+                self.quota_warn_percent.map(|p| p.to_string()),
+            ),
+            (args::STALE_RUNS, self.stale_runs.map(|n| n.to_string())),
+            (args::MAIL_ON, value_name(&self.mail_on)),
+            (args::BYPASS_LOCK, flag(self.bypass_lock)),
+            (
+                args::UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK,
+                flag(self.unknown_unencrypted_repo_access_is_ok),
+            ),
+            (
+                args::RELOCATED_REPO_ACCESS_IS_OK,
+                flag(self.relocated_repo_access_is_ok),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.filter(|v| !v.is_empty()).map(|v| (key, v)))
+        .collect()
+    }
+
+    /// The path of the repository, when `BORG_REPO` is a local path
+    pub fn local_path(&self) -> Option<PathBuf> {
+        match Location::parse(self.env.get("BORG_REPO")?) {
+            Location::Local(path) => Some(path),
+            Location::Remote { .. } => None,
+        }
+    }
+
+    /// True if no BORG_* variable provides a passphrase to access the repository
+    pub fn lacks_passphrase(&self) -> bool {
+        !["BORG_PASSPHRASE", "BORG_PASSCOMMAND", "BORG_PASSPHRASE_FD"]
+            .iter()
+            .any(|k| self.env.contains_key(*k))
+    }
+}
+
+/// The location of a repository as given in `BORG_REPO`
+#[derive(Debug, PartialEq, Eq)]
+pub enum Location {
+    /// A local path e.g. `/srv/borg` or `file:///srv/borg`
+    Local(PathBuf),
+    /// A remote repository e.g. `ssh://user@host:22/./repo`, `user@host:repo` or `rclone:remote:path`.
+    /// The host is unknown for backends without an authority (e.g. `rclone:` or `s3:`).
+    Remote { host: Option<String>, path: String },
+}
+
+/// Schemes of borg backends not addressed by a host e.g. `rclone:remote:path` or `s3:bucket/path`
+const OPAQUE_SCHEMES: [&str; 4] = ["rclone", "sftp", "s3", "b2"];
+
+impl Location {
+    /// Parse a `BORG_REPO` value. Unknown schemes are treated as remote with an unknown host.
+    pub fn parse(repo: &str) -> Self {
+        // URL style: `scheme://[user@]host[:port]/path`
+        if let Some((scheme, rest)) = repo.split_once("://") {
+            if scheme == "file" {
+                return Self::Local(PathBuf::from(rest));
+            }
+            let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+            let host = authority
+                .rsplit_once('@')
+                .map_or(authority, |(_, host)| host);
+            // Keep an IPv6 host in brackets intact, but strip a port
+            let host = match host.rfind(']') {
+                Some(end) => &host[..=end],
+                None => host.split(':').next().unwrap_or_default(),
+            };
+            return Self::Remote {
+                host: Some(host).filter(|h| !h.is_empty()).map(str::to_string),
+                path: path.to_string(),
+            };
+        }
+        // scp style: `[user@]host:path` or an opaque backend `scheme:...`
+        if let Some((prefix, path)) = repo.split_once(':') {
+            if !prefix.is_empty() && !prefix.contains('/') {
+                let host = prefix.rsplit_once('@').map_or(prefix, |(_, host)| host);
+                return Self::Remote {
+                    host: (!OPAQUE_SCHEMES.contains(&prefix)).then(|| host.to_string()),
+                    path: path.to_string(),
+                };
+            }
+        }
+        Self::Local(PathBuf::from(repo))
+    }
+
+    /// A name for the repository: The final component of the path or the host otherwise
+    pub fn name(&self) -> Option<String> {
+        match self {
+            Self::Local(path) => path
+                .file_name()
+                .and_then(std::ffi::OsStr::to_str)
+                .map(str::to_string),
+            Self::Remote { host, path } => path
+                .rsplit(['/', ':'])
+                .find(|c| !c.is_empty() && *c != ".")
+                .map(str::to_string)
+                .or_else(|| host.clone()),
+        }
+    }
+}
+
+/// Parse a list of space separated regular expressions
+fn parse_patterns(patterns: &str) -> Result<Vec<regex::Regex>, regex::Error> {
+    patterns.split_whitespace().map(regex::Regex::new).collect()
+}
+
+/// Parse a list of space separated `<KEY>=<VALUE>` pairs in the expected `format` e.g. `<GLOB>=<LABEL>`
+fn parse_pairs(pairs: &str, format: &str) -> Result<Vec<(String, String)>> {
+    pairs
+        .split_whitespace()
+        .map(|pair| {
+            pair.split_once('=')
+                .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .ok_or_else(|| anyhow!("Expected {format} but got '{pair}'"))
+        })
+        .collect()
+}
+
+/// Parse a list of space separated `<GLOB>=<LABEL>` pairs into labels keyed by their glob
+fn parse_glob_labels(labels: &str) -> Result<BTreeMap<String, String>> {
+    Ok(parse_pairs(labels, "<GLOB>=<LABEL>")?.into_iter().collect())
+}
+
+/// Parse a list of space separated `<GLOB>=<HOSTNAME>` pairs into hostnames keyed by their glob
+fn parse_glob_hostnames(hostnames: &str) -> Result<BTreeMap<String, String>> {
+    Ok(parse_pairs(hostnames, "<GLOB>=<HOSTNAME>")?
+        .into_iter()
+        .collect())
+}
+
+/// Parse the primary glob, which must be one of the `archive_globs`
+fn parse_primary_glob(glob: &str, archive_globs: &[String]) -> Result<String> {
+    ensure!(
+        archive_globs.iter().any(|g| g == glob),
+        "{glob:?} is not an archive glob"
+    );
+    Ok(glob.to_string())
+}
+
+/// Parse a list of space separated `<NAME>=<GLOB>` pairs of virtual repositories
+fn parse_virtual_repos(virtual_repos: &str) -> Result<Vec<(String, String)>> {
+    parse_pairs(virtual_repos, "<NAME>=<GLOB>")
+}
+
+/// Days of the week and of the month to run an operation e.g. "Sun 1"
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    weekdays: Vec<jiff::civil::Weekday>,
+    days: Vec<i8>,
+}
+
+impl Schedule {
+    /// True if the operation is due on the `date`
+    pub fn is_due(&self, date: jiff::civil::Date) -> bool {
+        self.weekdays.contains(&date.weekday()) || self.days.contains(&date.day())
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let weekdays = WEEKDAYS
+            .iter()
+            .filter(|(_, weekday)| self.weekdays.contains(weekday))
+            .map(|(name, _)| name[..3].to_string());
+        let days = self.days.iter().map(ToString::to_string);
+        write!(f, "{}", weekdays.chain(days).collect::<Vec<_>>().join(" "))
+    }
+}
+
+/// Names of the weekdays, which can be abbreviated to at least three letters
+const WEEKDAYS: [(&str, jiff::civil::Weekday); 7] = [
+    ("Monday", jiff::civil::Weekday::Monday),
+    ("Tuesday", jiff::civil::Weekday::Tuesday),
+    ("Wednesday", jiff::civil::Weekday::Wednesday),
+    ("Thursday", jiff::civil::Weekday::Thursday),
+    ("Friday", jiff::civil::Weekday::Friday),
+    ("Saturday", jiff::civil::Weekday::Saturday),
+    ("Sunday", jiff::civil::Weekday::Sunday),
+];
+
+/// Parse a list of space separated weekdays e.g. `Sun` and days of the month e.g. `1` into a `Schedule`
+fn parse_schedule(schedule: &str) -> Result<Schedule> {
+    let mut weekdays = Vec::new();
+    let mut days = Vec::new();
+    for day in schedule.split_whitespace() {
+        let weekday = WEEKDAYS.iter().find(|(name, _)| {
+            day.len() >= 3
+                && name
+                    .to_ascii_lowercase()
+                    .starts_with(&day.to_ascii_lowercase())
+        });
+        match (weekday, day.parse::<i8>()) {
+            (Some((_, weekday)), _) => weekdays.push(*weekday),
+            (None, Ok(day @ 1..=31)) => days.push(day),
+            _ => bail!("Expected a weekday e.g. 'Sun' or a day of the month 1-31 but got '{day}'"),
+        }
+    }
+    ensure!(
+        !(weekdays.is_empty() && days.is_empty()),
+        "Expected at least one weekday or day of the month"
+    );
+    Ok(Schedule { weekdays, days })
+}
+
+/// Group the indexed `vars` of the form `<PREFIX>_<INDEX>_<KEY>` into one env per repository.
+/// The BORG_* and BORGREPORT_* keys are kept e.g. `REPO_0_BORG_REPO` becomes `BORG_REPO` of group 0.
+/// The repository name is the value of `<PREFIX>_<INDEX>_NAME` or derived from its `BORG_REPO`.
+/// The groups are in order of their index.
+pub fn env_groups(
+    prefix: &str,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<(String, Env)>> {
+    let mut groups: BTreeMap<u32, (Option<String>, Env)> = BTreeMap::new();
+    for (key, value) in vars {
+        let Some((index, key)) = key
+            .strip_prefix(prefix)
+            .and_then(|key| key.strip_prefix('_'))
+            .and_then(|key| key.split_once('_'))
+        else {
+            continue;
+        };
+        let Ok(index) = index.parse::<u32>() else {
+            continue;
+        };
+        let group = groups.entry(index).or_default();
+        if key == "NAME" {
+            group.0 = Some(value);
+        } else if key.starts_with("BORG_") || key.starts_with("BORGREPORT_") {
+            group.1.insert(key.to_string(), value);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(index, (name, env))| {
+            let name = name
+                .or_else(|| {
+                    env.get("BORG_REPO")
+                        .and_then(|repo| Location::parse(repo).name())
+                })
+                .context(format!(
+                    "No {prefix}_{index}_NAME or {prefix}_{index}_BORG_REPO was provided"
+                ))?;
+            Ok((name, env))
+        })
+        .collect()
+}
+
+/// The BORGREPORT_* keys of the `env`, which do not match any known option e.g. a typo
+fn unknown_keys(env: &Env) -> Vec<String> {
+    let command = cli::command();
+    env.keys()
+        .filter(|key| key.starts_with("BORGREPORT_"))
+        .filter(|key| {
+            !command
+                .get_arguments()
+                .any(|arg| arg.get_id() == key.as_str())
+        })
+        .filter(|key| !args::ALIASES.iter().any(|(alias, _)| alias == key))
+        .cloned()
+        .collect()
+}
+
+/// Parse a list of space separated `<TIER>=<GLOB>` pairs into tiers keyed by their glob
+fn parse_tiers(tiers: &str) -> Result<BTreeMap<String, String>> {
+    Ok(parse_pairs(tiers, "<TIER>=<GLOB>")?
+        .into_iter()
+        .map(|(tier, glob)| (glob, tier))
+        .collect())
+}
+
+/// Format raw borg options as parsed by `parse_options`, as JSON array when an option contains spaces
+fn format_options(options: &[String]) -> String {
+    if options.iter().any(|o| o.contains(char::is_whitespace)) {
+        serde_json::to_string(options).unwrap_or_default()
+    } else {
+        options.join(" ")
+    }
+}
+
+/// The name of a `value` as given on the command line e.g. `warning`
+fn value_name(value: &impl clap::ValueEnum) -> Option<String> {
+    value.to_possible_value().map(|v| v.get_name().to_string())
+}
+
+/// Split a list of space separated raw borg options
+fn split_options(opts: &str) -> Vec<String> {
+    opts.split_whitespace()
+        .map(std::string::String::from)
+        .collect()
+}
+
+/// Parse a list of raw borg options either space separated or as JSON array of strings.
+/// The JSON array e.g. `["--glob-archives", "my archive-*"]` keeps options with spaces intact.
+fn parse_options(opts: &str) -> Result<Vec<String>> {
+    if opts.trim_start().starts_with('[') {
+        return serde_json::from_str(opts).context("Expected a JSON array of strings");
+    }
+    Ok(split_options(opts))
+}
+
+/// Append the global `--check-options-append` to the `options` of a repository
+fn append_check_options(mut options: Vec<String>) -> Result<Vec<String>> {
+    if let Some(opts) = &cli::args().check_opts_append {
+        options.extend(parse_options(opts).context(format!(
+            "Cannot parse parameter {}",
+            args::CHECK_OPTIONS_APPEND
+        ))?);
+    }
+    Ok(options)
+}
+
+/// True if the `borg prune` options contain a rule to keep archives e.g. `--keep-daily`
+fn has_keep_rule(options: &[String]) -> bool {
+    options.iter().any(|o| o.starts_with("--keep-"))
+}
+
+/// Options of `borg check`, which modify the repository
+pub(crate) const MODIFYING_CHECK_OPTIONS: [&str; 1] = ["--repair"];
+
+/// Apply the `policy` to the modifying `options` of `borg check` and describe them as warnings.
+/// Denied options are removed.
+/// Warnings for the `options` that are set, but ignored without a state directory
+fn guard_state_options(has_state_dir: bool, options: &[(&str, bool)]) -> Vec<String> {
+    options
+        .iter()
+        .filter(|(_, is_set)| *is_set && !has_state_dir)
+        .map(|(option, _)| {
+            format!(
+                "The option {option} compares with the previous runs and is ignored without {}.",
+                cli::args::STATE_DIR
+            )
+        })
+        .collect()
+}
+
+fn guard_check_options(options: Vec<String>, policy: CheckRepair) -> (Vec<String>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let options = options
+        .into_iter()
+        .filter(|option| {
+            if !MODIFYING_CHECK_OPTIONS.contains(&option.as_str()) {
+                return true;
+            }
+            match policy {
+                CheckRepair::Deny => warnings.push(format!(
+                    "The option {option} of `borg check` modifies the repository and was removed. Set {}=allow to run it.",
+                    args::CHECK_REPAIR
+                )),
+                CheckRepair::Warn => warnings.push(format!(
+                    "The option {option} of `borg check` modifies the repository. Set {}=allow to run it without this warning.",
+                    args::CHECK_REPAIR
+                )),
+                CheckRepair::Allow => {}
+            }
+            policy != CheckRepair::Deny
+        })
+        .collect();
+    (options, warnings)
+}
+
+/// Parse the raw `borg info` options, which must not conflict with the options set by borgreport
+fn parse_info_options(opts: &str) -> Result<Vec<String>> {
+    let options = parse_options(opts)?;
+    if let Some(option) = options.iter().find(|o| {
+        ["--json", "--last", "--first"]
+            .iter()
+            .any(|reserved| o.split('=').next() == Some(*reserved))
+    }) {
+        bail!("The option {option} is set by borgreport and cannot be used");
+    }
+    Ok(options)
+}
+
+/// The global mail policy from the CLI or the global env
+pub fn global_mail_on() -> Result<MailOn> {
+    Ok(arg(&Env::new(), args::MAIL_ON)?.unwrap_or_default())
+}
+
 /// Check the CLI, the global env and the given env (a repo env) for the argument
 fn arg<T>(env: &Env, id: &str) -> Result<Option<T>>
 where
@@ -115,6 +852,16 @@ where
     T::from_repo_arg(env, id)
 }
 
+/// The variable names of the option `id` followed by its aliases
+fn env_keys(id: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(id).chain(
+        args::ALIASES
+            .iter()
+            .filter(move |(_, option)| *option == id)
+            .map(|(alias, _)| *alias),
+    )
+}
+
 /// Construct a value from a CLI or ENV value
 trait FromArg {
     type Value;
@@ -150,7 +897,7 @@ macro_rules! from_arg_impl {
             }
             /// CLI Environment as $type parsed via clap
             fn from_cli_env(id: &str) -> Result<Option<Self::Value>> {
-                if let Some(value) = std::env::var_os(id) {
+                if let Some(value) = env_keys(id).find_map(std::env::var_os) {
                     return Ok(Some(clap_parse::<$type>(
                         id,
                         clap::value_parser!($type),
@@ -161,7 +908,7 @@ macro_rules! from_arg_impl {
             }
             /// Repository Environment as $type parsed via clap
             fn from_repo_env(env: &borg::Env, id: &str) -> Result<Option<Self::Value>> {
-                if let Some(value) = env.get(id) {
+                if let Some(value) = env_keys(id).find_map(|key| env.get(key)) {
                     return Ok(Some(clap_parse::<$type>(
                         id,
                         clap::value_parser!($type),
@@ -175,8 +922,14 @@ macro_rules! from_arg_impl {
 }
 from_arg_impl! {bool}
 from_arg_impl! {f64}
+from_arg_impl! {usize}
+from_arg_impl! {u64}
 from_arg_impl! {String}
 from_arg_impl! {PathBuf}
+from_arg_impl! {MailOn}
+from_arg_impl! {LastBy}
+from_arg_impl! {Severity}
+from_arg_impl! {CheckRepair}
 
 /// Parse the argument `value` with `parser`. Use `id` as argument name in error.
 fn clap_parse<T: std::any::Any + Clone + Send + Sync + 'static>(
@@ -195,3 +948,230 @@ fn clap_parse<T: std::any::Any + Clone + Send + Sync + 'static>(
         "Cannot parse {id}: The parser does not match the type!",
     ))
 }
+
+mod tests {
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn parse_options() {
+        use super::parse_options;
+
+        assert_eq!(
+            parse_options(" --verify-data  --max-duration 60 ").unwrap(),
+            ["--verify-data", "--max-duration", "60"]
+        );
+        assert_eq!(
+            parse_options(r#"["--glob-archives", "my archive-*"]"#).unwrap(),
+            ["--glob-archives", "my archive-*"]
+        );
+        assert!(parse_options(r#"["--verify-data", 1]"#).is_err());
+        assert!(parse_options("[--verify-data").is_err());
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn format_options() {
+        use super::{format_options, parse_options};
+
+        for options in [
+            "--verify-data --max-duration 60",
+            r#"["--glob-archives","my archive-*"]"#,
+        ] {
+            assert_eq!(format_options(&parse_options(options).unwrap()), options);
+        }
+    }
+
+    #[test]
+    fn location() {
+        use super::Location;
+        use std::path::PathBuf;
+
+        let remote = |host: Option<&str>, path: &str| Location::Remote {
+            host: host.map(str::to_string),
+            path: path.to_string(),
+        };
+
+        assert_eq!(
+            Location::parse("/srv/borg"),
+            Location::Local(PathBuf::from("/srv/borg"))
+        );
+        assert_eq!(
+            Location::parse("file:///srv/borg"),
+            Location::Local(PathBuf::from("/srv/borg"))
+        );
+        assert_eq!(
+            Location::parse("ssh://user@host:22/./repo"),
+            remote(Some("host"), "./repo")
+        );
+        assert_eq!(
+            Location::parse("ssh://user@[::1]:22/repo"),
+            remote(Some("[::1]"), "repo")
+        );
+        assert_eq!(
+            Location::parse("user@host:repo"),
+            remote(Some("host"), "repo")
+        );
+        assert_eq!(
+            Location::parse("sftp://user@host/backups/repo"),
+            remote(Some("host"), "backups/repo")
+        );
+        assert_eq!(Location::parse("sftp:repo"), remote(None, "repo"));
+        assert_eq!(
+            Location::parse("user@sftp:repo"),
+            remote(Some("sftp"), "repo")
+        );
+        assert_eq!(
+            Location::parse("rclone:remote:backups/repo"),
+            remote(None, "remote:backups/repo")
+        );
+        assert_eq!(
+            Location::parse("s3:bucket/repo"),
+            remote(None, "bucket/repo")
+        );
+
+        assert_eq!(Location::parse("/srv/borg").name().as_deref(), Some("borg"));
+        assert_eq!(
+            Location::parse("ssh://host/./repo/").name().as_deref(),
+            Some("repo")
+        );
+        assert_eq!(
+            Location::parse("ssh://host/").name().as_deref(),
+            Some("host")
+        );
+        assert_eq!(
+            Location::parse("rclone:remote:").name().as_deref(),
+            Some("remote")
+        );
+        assert_eq!(
+            Location::parse("rclone:remote:backups/repo")
+                .name()
+                .as_deref(),
+            Some("repo")
+        );
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn parse_schedule() {
+        use crate::repository::parse_schedule;
+        use jiff::civil::date;
+        let schedule = parse_schedule("Sun 1 monday").unwrap();
+        assert_eq!(schedule.to_string(), "Mon Sun 1");
+        assert!(schedule.is_due(date(2024, 9, 1))); // Sunday and first of the month
+        assert!(schedule.is_due(date(2024, 10, 1))); // Tuesday
+        assert!(schedule.is_due(date(2024, 9, 2))); // Monday
+        assert!(!schedule.is_due(date(2024, 9, 3))); // Tuesday
+        assert!(parse_schedule("32").is_err());
+        assert!(parse_schedule("Sunny").is_err());
+        assert!(parse_schedule("").is_err());
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn env_groups() {
+        use super::env_groups;
+
+        let vars = [
+            ("REPO_1_BORG_REPO", "ssh://backup@nas/./web"),
+            ("REPO_1_BORGREPORT_CHECK", "true"),
+            ("REPO_0_NAME", "db"),
+            ("REPO_0_BORG_REPO", "/srv/borg/database"),
+            ("REPO_0_OTHER", "ignored"),
+            ("REPO_X_BORG_REPO", "ignored"),
+            ("REPOSITORY_0_BORG_REPO", "ignored"),
+            ("BORG_REPO", "ignored"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let groups = env_groups("REPO", vars).unwrap();
+        let names: Vec<&str> = groups.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["db", "web"]);
+        assert_eq!(groups[0].1.keys().collect::<Vec<_>>(), ["BORG_REPO"]);
+        assert_eq!(groups[1].1.len(), 2);
+
+        assert!(env_groups("REPO", [("REPO_0_BORG_PASSPHRASE".into(), "secret".into())]).is_err());
+    }
+
+    #[test]
+    fn has_keep_rule() {
+        use super::has_keep_rule;
+
+        let options = |opts: &[&str]| opts.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert!(has_keep_rule(&options(&["--keep-daily", "7"])));
+        assert!(has_keep_rule(&options(&[
+            "--save-space",
+            "--keep-within=2d"
+        ])));
+        assert!(!has_keep_rule(&options(&["--save-space"])));
+        assert!(!has_keep_rule(&[]));
+    }
+
+    #[test]
+    fn guard_state_options() {
+        use super::{args, guard_state_options};
+
+        let options = [
+            (args::CHECK_MAX_INTERVAL_HOURS, true),
+            (args::STALE_RUNS, false),
+        ];
+        assert!(guard_state_options(true, &options).is_empty());
+        let warnings = guard_state_options(false, &options);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains(args::CHECK_MAX_INTERVAL_HOURS));
+    }
+
+    #[test]
+    fn guard_check_options() {
+        use super::guard_check_options;
+        use crate::cli::CheckRepair;
+
+        let options = || vec!["--verify-data".to_string(), "--repair".to_string()];
+        let (denied, warnings) = guard_check_options(options(), CheckRepair::Deny);
+        assert_eq!(
+            (denied, warnings.len()),
+            (vec!["--verify-data".to_string()], 1)
+        );
+        let (warned, warnings) = guard_check_options(options(), CheckRepair::Warn);
+        assert_eq!((warned, warnings.len()), (options(), 1));
+        let (allowed, warnings) = guard_check_options(options(), CheckRepair::Allow);
+        assert_eq!((allowed, warnings.len()), (options(), 0));
+    }
+
+    #[test]
+    fn unknown_keys() {
+        use super::unknown_keys;
+
+        let env = [
+            ("BORG_REPO", "/srv/borg"),
+            ("BORGREPORT_CHECK", "true"),
+            ("BORGREPORT_CHEK", "true"),
+            ("BORGREPORT_RELOCATED_REPO_OK", "true"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(unknown_keys(&env), ["BORGREPORT_CHEK"]);
+    }
+
+    #[test]
+    fn env_keys() {
+        use super::env_keys;
+
+        assert_eq!(
+            env_keys("BORGREPORT_RELOCATED_REPO_ACCESS_IS_OK").collect::<Vec<_>>(),
+            [
+                "BORGREPORT_RELOCATED_REPO_ACCESS_IS_OK",
+                "BORGREPORT_RELOCATED_REPO_OK"
+            ]
+        );
+        assert_eq!(
+            env_keys("BORGREPORT_UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK").collect::<Vec<_>>(),
+            [
+                "BORGREPORT_UNKNOWN_UNENCRYPTED_REPO_ACCESS_IS_OK",
+                "BORGREPORT_UNKNOWN_UNENCRYPTED_REPO_OK"
+            ]
+        );
+        assert_eq!(
+            env_keys("BORGREPORT_CHECK").collect::<Vec<_>>(),
+            ["BORGREPORT_CHECK"]
+        );
+    }
+}
@@ -0,0 +1,264 @@
+// SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Number of runs to keep in the history of a repository
+const HISTORY_LEN: usize = 400;
+
+/// The persisted state of a repository between two runs
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct State {
+    /// Previous runs with the oldest first
+    pub history: Vec<Run>,
+    /// Archives checked in the current sampling cycle per archive glob
+    #[serde(default)]
+    pub checked: BTreeMap<String, Vec<String>>,
+    /// Time of the last run with a successful `borg check` of all selected archives
+    #[serde(default)]
+    pub last_check_success: Option<jiff::Timestamp>,
+    /// True if all checks of the last run with `borg check` passed
+    #[serde(default)]
+    pub last_check_passed: Option<bool>,
+}
+
+/// The outcome of a single run for a repository
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Run {
+    /// Time of the run
+    pub timestamp: jiff::Timestamp,
+    /// Total deduplicated compressed repository size
+    pub unique_csize: i64,
+    /// Last modification time of the repository
+    #[serde(default)]
+    pub last_modified: Option<jiff::civil::DateTime>,
+    /// Number of errors reported for the repository
+    #[serde(default)]
+    pub errors: usize,
+    /// Number of warnings reported for the repository
+    #[serde(default)]
+    pub warnings: usize,
+}
+
+impl State {
+    /// Path of the state file for the repository `name` in `state_dir`
+    fn path(state_dir: &Path, name: &str) -> PathBuf {
+        state_dir.join(format!("{}.json", name.replace('/', "_")))
+    }
+
+    /// Load the state of the repository `name`. A missing state file yields an empty `State`.
+    pub fn load(state_dir: &Path, name: &str) -> Result<Self> {
+        let path = Self::path(state_dir, name);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .context(format!("Cannot parse the state file {path:?}")),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).context(format!("Cannot read the state file {path:?}")),
+        }
+    }
+
+    /// Save the state of the repository `name` atomically, a crash never leaves a partial file
+    pub fn save(&self, state_dir: &Path, name: &str) -> Result<()> {
+        let path = Self::path(state_dir, name);
+        std::fs::create_dir_all(state_dir)
+            .context(format!("Cannot create the state directory {state_dir:?}"))?;
+        crate::utils::write_file(&path, &serde_json::to_string(self)?)
+            .context(format!("Cannot write the state file {path:?}"))
+    }
+
+    /// The most recent run
+    pub fn last(&self) -> Option<&Run> {
+        self.history.last()
+    }
+
+    /// Select `n` random `archives` of the `archive_glob` to check. Archives not checked in the
+    /// current cycle are preferred. A new cycle starts, when all archives have been checked.
+    pub fn sample_archives(
+        &mut self,
+        archive_glob: Option<&str>,
+        archives: &[String],
+        n: usize,
+    ) -> Vec<String> {
+        let checked = self
+            .checked
+            .entry(archive_glob.unwrap_or_default().to_string())
+            .or_default();
+        // Forget about pruned archives
+        checked.retain(|name| archives.contains(name));
+
+        let mut unchecked: Vec<&String> =
+            archives.iter().filter(|a| !checked.contains(a)).collect();
+        fastrand::shuffle(&mut unchecked);
+        let mut sample: Vec<String> = unchecked.into_iter().take(n).cloned().collect();
+
+        if sample.len() < n {
+            // All archives have been checked: Start a new cycle
+            checked.clear();
+            let mut others: Vec<&String> =
+                archives.iter().filter(|a| !sample.contains(a)).collect();
+            fastrand::shuffle(&mut others);
+            sample.extend(others.into_iter().take(n - sample.len()).cloned());
+        }
+        checked.extend(sample.iter().cloned());
+        sample
+    }
+
+    /// Number of the most recent runs, which saw the repository with the same `last_modified` time
+    pub fn unmodified_runs(&self, last_modified: jiff::civil::DateTime) -> usize {
+        self.history
+            .iter()
+            .rev()
+            .take_while(|run| run.last_modified == Some(last_modified))
+            .count()
+    }
+
+    /// Add a `run` to the history and drop the oldest runs exceeding the `HISTORY_LEN`
+    pub fn push(&mut self, run: Run) {
+        self.history.push(run);
+        if self.history.len() > HISTORY_LEN {
+            self.history.drain(..self.history.len() - HISTORY_LEN);
+        }
+    }
+}
+
+/// Best-effort access to the state directory for a single run.
+/// The first failed read or write of the directory is reported and disables the state for the
+/// rest of the run. A state file, which cannot be parsed, affects only its repository.
+pub struct Store {
+    /// The state directory or `None` without state
+    dir: Option<PathBuf>,
+    /// True after an I/O error in the state directory
+    disabled: Cell<bool>,
+}
+
+impl Store {
+    /// Access the state in `dir` or nothing for `None`
+    pub fn new(dir: Option<&Path>) -> Self {
+        Self {
+            dir: dir.map(Path::to_path_buf),
+            disabled: Cell::new(false),
+        }
+    }
+
+    /// True if the state directory is present and not disabled
+    pub fn is_enabled(&self) -> bool {
+        self.dir().is_some()
+    }
+
+    /// The state directory, unless absent or disabled
+    fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref().filter(|_| !self.disabled.get())
+    }
+
+    /// Load the state of the repository `name` or `None` without an (enabled) state directory
+    pub fn load(&self, name: &str) -> Result<Option<State>> {
+        let Some(dir) = self.dir() else {
+            return Ok(None);
+        };
+        State::load(dir, name).map(Some).map_err(|e| self.failed(e))
+    }
+
+    /// Save the `state` of the repository `name`. Nothing is saved after a failure.
+    pub fn save(&self, state: &State, name: &str) -> Result<()> {
+        let Some(dir) = self.dir() else {
+            return Ok(());
+        };
+        state.save(dir, name).map_err(|e| self.failed(e))
+    }
+
+    /// Disable the state on an I/O error
+    fn failed(&self, e: anyhow::Error) -> anyhow::Error {
+        if e.root_cause().downcast_ref::<std::io::Error>().is_some() {
+            self.disabled.set(true);
+            e.context("The state is disabled for the remaining repositories of this run")
+        } else {
+            e
+        }
+    }
+}
+
+mod tests {
+    #[test]
+    fn sample_archives() {
+        use crate::state::State;
+        let archives: Vec<String> = (1..=5).map(|i| format!("archive-{i}")).collect();
+        let mut state = State::default();
+
+        // The first cycle covers all archives without repetition
+        let mut sampled = state.sample_archives(None, &archives, 2);
+        sampled.extend(state.sample_archives(None, &archives, 2));
+        let last = state.sample_archives(None, &archives, 2);
+        sampled.extend(
+            last.iter()
+                .filter(|a| !sampled.contains(a))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        sampled.sort();
+        assert_eq!(sampled, archives);
+
+        // A pruned archive is forgotten and the sample never exceeds the archives
+        assert_eq!(
+            state.sample_archives(None, &archives[..1], 2),
+            archives[..1]
+        );
+    }
+
+    #[test]
+    fn unmodified_runs() {
+        use crate::state::{Run, State};
+        let modified = jiff::civil::date(2024, 8, 5).at(1, 48, 43, 0);
+        let unmodified = jiff::civil::date(2024, 8, 6).at(1, 48, 43, 0);
+        let mut state = State::default();
+        for last_modified in [None, Some(modified), Some(unmodified), Some(unmodified)] {
+            state.push(Run {
+                timestamp: jiff::Timestamp::UNIX_EPOCH,
+                unique_csize: 0,
+                last_modified,
+                errors: 0,
+                warnings: 0,
+            });
+        }
+        assert_eq!(state.unmodified_runs(unmodified), 2);
+        assert_eq!(state.unmodified_runs(modified), 0);
+    }
+
+    #[test]
+    fn store() {
+        use crate::state::{State, Store};
+        let dir = std::env::temp_dir().join(format!("borgreport-store-{}", std::process::id()));
+        #[allow(clippy::unwrap_used)]
+        std::fs::write(&dir, "").unwrap();
+
+        // A file as state directory fails once and disables the store
+        let store = Store::new(Some(&dir.join("state")));
+        assert!(store.save(&State::default(), "repo").is_err());
+        assert!(store.save(&State::default(), "repo").is_ok());
+        assert!(store.load("repo").is_ok_and(|state| state.is_none()));
+        let _ = std::fs::remove_file(&dir);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn broken_state_file() {
+        use crate::state::{State, Store};
+        let dir = std::env::temp_dir().join(format!("borgreport-broken-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("repo.json"), "{\"history\": [").unwrap();
+
+        // A state file, which cannot be parsed, keeps the store enabled to replace the file
+        let store = Store::new(Some(&dir));
+        assert!(store.load("repo").is_err());
+        assert!(store.is_enabled());
+        store.save(&State::default(), "repo").unwrap();
+        assert!(store.load("repo").is_ok_and(|state| state.is_some()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
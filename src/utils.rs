@@ -1,10 +1,32 @@
 // SPDX-FileCopyrightText: 2024 Philipp Micheel <bbx0+borgreport@bitdevs.de>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use lettre::{
     address::Envelope, message::MultiPart, Address, Message, SendmailTransport, Transport,
 };
+use std::sync::{Arc, Mutex};
+
+/// The available and the total size in bytes of the filesystem containing the `path`
+#[allow(clippy::useless_conversion)] // The field types of `statvfs` differ by platform
+pub fn free_space(path: &std::path::Path) -> std::io::Result<(u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is initialized on success
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+    let fragment_size = u64::from(stat.f_frsize);
+    Ok((
+        u64::from(stat.f_bavail) * fragment_size,
+        u64::from(stat.f_blocks) * fragment_size,
+    ))
+}
 
 /// A simple `sendmail` wrapper expecting the body in plain text and html format
 pub fn send_mail(
@@ -42,3 +64,80 @@ pub fn send_mail(
     SendmailTransport::new().send(&message)?;
     Ok(())
 }
+
+/// The latest report in all formats served via HTTP
+pub struct Published {
+    pub html: String,
+    pub metrics: String,
+}
+
+/// Serve the `latest` report on `addr` in a background thread:
+/// `/metrics` in `OpenMetrics` format and `/` in HTML
+pub fn serve(addr: &str, latest: Arc<Mutex<Option<Published>>>) -> Result<()> {
+    let server =
+        tiny_http::Server::http(addr).map_err(|e| anyhow!("Cannot listen on {addr}: {e}"))?;
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let (status, content_type, body) = match (request.url(), latest.lock().as_deref()) {
+                ("/metrics", Ok(Some(published))) => (
+                    200,
+                    "application/openmetrics-text; version=1.0.0; charset=utf-8",
+                    published.metrics.clone(),
+                ),
+                ("/", Ok(Some(published))) => {
+                    (200, "text/html; charset=utf-8", published.html.clone())
+                }
+                ("/metrics" | "/", _) => (
+                    503,
+                    "text/plain; charset=utf-8",
+                    "The report is not available yet.".to_string(),
+                ),
+                _ => (404, "text/plain; charset=utf-8", "Not found.".to_string()),
+            };
+            let mut response = tiny_http::Response::from_string(body).with_status_code(status);
+            if let Ok(header) = tiny_http::Header::from_bytes("Content-Type", content_type) {
+                response.add_header(header);
+            }
+            // A failed response only affects the client
+            let _ = request.respond(response);
+        }
+    });
+    Ok(())
+}
+
+/// Write the `contents` atomically into the `file` via a temporary file in the same directory.
+/// A reader never sees a partially written file. A `file` ending in `.gz` is gzip compressed.
+pub fn write_file(file: &std::path::Path, contents: &str) -> Result<()> {
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file.file_name().unwrap_or_default());
+    temp_name.push(".tmp");
+    let temp = file.with_file_name(temp_name);
+    let contents = if file.extension().is_some_and(|ext| ext == "gz") {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(contents.as_bytes())?;
+        encoder.finish()?
+    } else {
+        contents.as_bytes().to_vec()
+    };
+    std::fs::write(&temp, contents).context(format!("Cannot write file {temp:?}"))?;
+    std::fs::rename(&temp, file).context(format!("Cannot write file {file:?}"))
+}
+
+mod tests {
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn write_file() {
+        use super::write_file;
+        use std::io::Read;
+
+        let file = std::env::temp_dir().join(format!("borgreport-{}.txt.gz", std::process::id()));
+        write_file(&file, "==== Backup report ====\n").unwrap();
+        let mut contents = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(&file).unwrap())
+            .read_to_string(&mut contents)
+            .unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(contents, "==== Backup report ====\n");
+    }
+}
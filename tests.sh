@@ -153,6 +153,64 @@ test5() {
 	esac
 }
 
+# Test 6: Repo relocated
+# Prepare:	test6 prepare
+# Run:			test6 run <REPORT>
+# Returns 0 on success
+test6() {
+	local name="${FUNCNAME[0]}-relocated"
+	case ${1} in
+	prepare)
+		make_env "${TEST_DIR}/${name}.env" <<-EOF
+			BORG_BASE_DIR=${BASE_DIR}
+			BORG_REPO=${TEST_DIR}/${name}-before-move
+			BORG_PASSPHRASE=${name}
+		EOF
+		borg init --encryption=repokey &>/dev/null
+		borg create '::{utcnow}Z' "${BASH_SOURCE[0]}"
+		mv "${TEST_DIR}/${name}-before-move" "${TEST_DIR}/${name}"
+		make_env "${TEST_DIR}/${name}.env" <<-EOF
+			BORG_BASE_DIR=${BASE_DIR}
+			BORG_REPO=${TEST_DIR}/${name}
+			BORG_PASSPHRASE=${name}
+			BORGREPORT_RELOCATED_REPO_OK=true
+		EOF
+		;;
+	run)
+		grep --silent -E -e "^\| ${name} +\|[^|]+\| [0-9]{4}-[0-9]{2}-[0-9]{2}T" <<<"${2}"
+		;;
+	*) echo "Wrong call to ${FUNCNAME[0]}. Unknown action." >&2 && exit 1 ;;
+	esac
+}
+
+# Test 7: Repo unknown and unencrypted
+# Prepare:	test7 prepare
+# Run:			test7 run <REPORT>
+# Returns 0 on success
+test7() {
+	local name="${FUNCNAME[0]}-unknownunencrypted"
+	case ${1} in
+	prepare)
+		# The repository is created with another base dir, so it is unknown to the report
+		make_env "${TEST_DIR}/${name}.env" <<-EOF
+			BORG_BASE_DIR=${TEST_DIR}/${name}-base
+			BORG_REPO=${TEST_DIR}/${name}
+		EOF
+		borg init --encryption=none &>/dev/null
+		borg create '::{utcnow}Z' "${BASH_SOURCE[0]}"
+		make_env "${TEST_DIR}/${name}.env" <<-EOF
+			BORG_BASE_DIR=${BASE_DIR}
+			BORG_REPO=${TEST_DIR}/${name}
+			BORGREPORT_UNKNOWN_UNENCRYPTED_REPO_OK=true
+		EOF
+		;;
+	run)
+		grep --silent -E -e "^\| ${name} +\|[^|]+\| [0-9]{4}-[0-9]{2}-[0-9]{2}T" <<<"${2}"
+		;;
+	*) echo "Wrong call to ${FUNCNAME[0]}. Unknown action." >&2 && exit 1 ;;
+	esac
+}
+
 # Prepare test <NUMBER> for execution
 # Usage: test <NUMBER>
 prepare_test() {